@@ -0,0 +1,114 @@
+use std::convert::Infallible;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use futures::executor::block_on;
+use futures::{stream, StreamExt, TryStreamExt};
+
+/// The `Vec` backed splitter `Lines` used before lines were split off
+/// of a `BytesMut` in place, kept around as a baseline
+fn naive(chunks: &[Vec<u8>]) -> usize {
+    fn next(
+        buffered: &mut Option<Vec<u8>>,
+        flush: bool,
+    ) -> Option<Vec<u8>> {
+        let buffer = buffered.take()?;
+        let mut split = buffer.splitn(2, |c| *c == b'\n');
+        if let Some(first) = split.next() {
+            let mut line = first.to_vec();
+            if let Some(&b'\r') = line.last() {
+                line.pop();
+            }
+            if let Some(second) = split.next() {
+                *buffered = Some(second.to_vec());
+                return Some(line);
+            } else if flush {
+                return Some(line);
+            }
+        }
+        *buffered = Some(buffer);
+        None
+    }
+
+    let mut buffered: Option<Vec<u8>> = None;
+    let mut count = 0;
+    for chunk in chunks {
+        match buffered {
+            Some(ref mut buffer) => buffer.extend_from_slice(chunk),
+            None => buffered = Some(chunk.clone()),
+        }
+        while next(&mut buffered, false).is_some() {
+            count += 1;
+        }
+    }
+    while next(&mut buffered, true).is_some() {
+        count += 1;
+    }
+    count
+}
+
+fn lines(chunks: Vec<Vec<u8>>) -> usize {
+    let chunks = stream::iter(chunks).map(Ok::<_, Infallible>);
+    block_on(stream_lines::bytes(chunks).try_fold(0, |count, _| async move { Ok(count + 1) }))
+        .unwrap()
+}
+
+fn lines_ref(chunks: Vec<Vec<u8>>) -> usize {
+    let chunks = stream::iter(chunks).map(Ok::<_, Infallible>);
+    let mut count = 0;
+    block_on(stream_lines::bytes(chunks).for_each_line(|_| count += 1)).unwrap();
+    count
+}
+
+/// `count` lines of `width` bytes, re-chunked into `chunk` sized pieces
+fn input(
+    count: usize,
+    width: usize,
+    chunk: usize,
+) -> Vec<Vec<u8>> {
+    let mut line = vec![b'x'; width];
+    line.push(b'\n');
+    line.repeat(count)
+        .chunks(chunk)
+        .map(<[u8]>::to_vec)
+        .collect()
+}
+
+fn throughput(c: &mut Criterion) {
+    for &(name, count, width, chunk) in &[
+        ("short lines in large chunks", 10_000, 16, 64 * 1024),
+        ("long lines in small chunks", 100, 4 * 1024, 512),
+    ] {
+        let chunks = input(count, width, chunk);
+        let mut group = c.benchmark_group(name);
+        group.throughput(Throughput::Bytes((count * (width + 1)) as u64));
+        group.bench_function("naive", |b| b.iter(|| naive(&chunks)));
+        group.bench_function("lines", |b| {
+            b.iter_batched(|| chunks.clone(), lines, BatchSize::SmallInput)
+        });
+        group.bench_function("for_each_line", |b| {
+            b.iter_batched(|| chunks.clone(), lines_ref, BatchSize::SmallInput)
+        });
+        group.finish();
+    }
+}
+
+fn long_line(c: &mut Criterion) {
+    let mut line = vec![b'x'; 10 * 1024 * 1024];
+    line.push(b'\n');
+    let mut group = c.benchmark_group("10 MB line in 1 byte chunks");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(line.len() as u64));
+    group.bench_function("lines", |b| {
+        b.iter(|| {
+            let chunks = stream::iter(line.chunks(1)).map(Ok::<_, Infallible>);
+            block_on(
+                stream_lines::bytes(chunks).try_fold(0, |count, _| async move { Ok(count + 1) }),
+            )
+            .unwrap()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, throughput, long_line);
+criterion_main!(benches);
@@ -1,31 +1,54 @@
-#[macro_use]
-extern crate failure;
-extern crate futures;
-extern crate hyper;
-extern crate hyper_tls;
-extern crate stream_lines;
-extern crate tokio;
-
 use std::error::Error;
-use std::string::FromUtf8Error;
+use std::fmt;
 
-use failure::Fail;
-use futures::{Future, Stream};
-use hyper::{Body, Client, Request};
+use futures::{StreamExt, TryStreamExt};
+use http_body_util::{BodyExt, Empty};
+use hyper::body::Bytes;
+use hyper::Request;
 use hyper_tls::HttpsConnector;
-use tokio::runtime::Runtime;
+use hyper_util::client::legacy::{self, Client};
+use hyper_util::rt::TokioExecutor;
+use stream_lines::{sse, LineTooLong};
 
-#[derive(Debug, Fail)]
+#[derive(Debug)]
 enum AppErr {
-    #[fail(display = "encoding error: {}", _0)]
-    Utf8(#[cause] FromUtf8Error),
-    #[fail(display = "http error: {}", _0)]
-    Http(#[cause] hyper::Error),
+    TooLong(LineTooLong),
+    Connect(legacy::Error),
+    Http(hyper::Error),
+}
+
+impl fmt::Display for AppErr {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        match self {
+            AppErr::TooLong(e) => write!(f, "framing error: {}", e),
+            AppErr::Connect(e) => write!(f, "connection error: {}", e),
+            AppErr::Http(e) => write!(f, "http error: {}", e),
+        }
+    }
+}
+
+impl Error for AppErr {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AppErr::TooLong(e) => Some(e),
+            AppErr::Connect(e) => Some(e),
+            AppErr::Http(e) => Some(e),
+        }
+    }
+}
+
+impl From<LineTooLong> for AppErr {
+    fn from(e: LineTooLong) -> Self {
+        AppErr::TooLong(e)
+    }
 }
 
-impl From<FromUtf8Error> for AppErr {
-    fn from(e: FromUtf8Error) -> Self {
-        AppErr::Utf8(e)
+impl From<legacy::Error> for AppErr {
+    fn from(e: legacy::Error) -> Self {
+        AppErr::Connect(e)
     }
 }
 
@@ -35,28 +58,30 @@ impl From<hyper::Error> for AppErr {
     }
 }
 
-fn run() -> Result<(), Box<Error>> {
-    let connector = HttpsConnector::new(4).expect("unable to initialize https connector");
-    let http = Client::builder()
-        .keep_alive(true)
-        .build::<_, Body>(connector);
-
-    let req = Request::get("https://stream.wikimedia.org/v2/stream/recentchange")
-        .header("Accept", "text/event-stream")
-        .body(Default::default())?;
-    let work = http.request(req).map_err(AppErr::from).and_then(|resp| {
-        stream_lines::strings(resp.into_body().map_err(AppErr::from))
-            .for_each(|line| Ok(println!("-> {}", line)))
-    });
-    Runtime::new()
-        .expect("failed to initialize runtime")
-        .block_on(work)
-        .map_err(AppErr::compat)?;
-    Ok(())
-}
+#[tokio::main]
+async fn main() {
+    let http =
+        Client::builder(TokioExecutor::new()).build::<_, Empty<Bytes>>(HttpsConnector::new());
 
-fn main() {
-    if let Err(e) = run() {
-        eprintln!("error: {}", e)
-    }
+    sse::reconnect(move |last_event_id: Option<String>| {
+        let mut req = Request::get("https://stream.wikimedia.org/v2/stream/recentchange")
+            .header("Accept", "text/event-stream");
+        if let Some(id) = last_event_id {
+            req = req.header("Last-Event-ID", id);
+        }
+        let req = req.body(Empty::new()).expect("invalid request");
+        let http = http.clone();
+        async move {
+            let resp = http.request(req).await?;
+            Ok(resp.into_body().into_data_stream().map_err(AppErr::from))
+        }
+    })
+    .max_line_length(64 * 1024)
+    .for_each(|event| async move {
+        match event {
+            Ok(event) => println!("-> {} {}", event.event, event.data),
+            Err(e) => eprintln!("error: {}, reconnecting", e),
+        }
+    })
+    .await
 }
@@ -1,19 +1,16 @@
-extern crate futures;
-extern crate stream_lines;
-extern crate tokio;
-
 use std::string::FromUtf8Error;
 
-use futures::stream::iter_ok;
-use futures::Stream;
-use tokio::runtime::Runtime;
+use futures::{stream, StreamExt, TryStreamExt};
 
-fn main() {
+#[tokio::main]
+async fn main() {
     let chunks = vec!["\nhello ", "world\n", "\n", "what a\nlovely", "\nday\n"];
-    let stream = iter_ok::<_, FromUtf8Error>(chunks);
-    let print = stream_lines::strings(stream).for_each(|line| Ok(println!("{}", line)));
-    Runtime::new()
-        .expect("failed to initialize runtime")
-        .block_on(print)
+    let stream = stream::iter(chunks).map(Ok::<_, FromUtf8Error>);
+    stream_lines::strings(stream)
+        .try_for_each(|line| async move {
+            println!("{}", line);
+            Ok(())
+        })
+        .await
         .expect("failed to execute stream");
 }
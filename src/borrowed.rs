@@ -0,0 +1,79 @@
+use std::future::poll_fn;
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll};
+
+use futures_core::{ready, Stream};
+
+use crate::{Limit, Lines};
+
+impl<S, O, E, F, L> Lines<S, O, E, F, L> {
+    /// Polls for the next line, lending it out of the internal buffer rather than
+    /// converting it, so lines that are only inspected are never copied or allocated
+    /// for. The line is only borrowed until the next poll, at which point its bytes
+    /// may be reused for those read after it.
+    pub fn poll_line_ref<C, SE>(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<&[u8], SE>>>
+    where
+        S: Stream<Item = Result<C, SE>>,
+        C: AsRef<[u8]>,
+        L: Limit<SE>,
+    {
+        // release the last line so that the buffer it was split off of can be reclaimed
+        *self.as_mut().project().lent = None;
+        let line = ready!(self.as_mut().poll_raw(cx));
+        let lent = self.project().lent;
+        Poll::Ready(match line {
+            Some(Ok(raw)) => Some(Ok(lent.insert(raw.line))),
+            Some(Err(err)) => Some(Err(err)),
+            None => None,
+        })
+    }
+
+    /// Calls `f` with each line, lent out of the internal buffer as with `poll_line_ref`,
+    /// until the stream ends or fails
+    ///
+    /// ```
+    /// use std::io;
+    ///
+    /// use futures::{stream, StreamExt};
+    ///
+    /// # futures::executor::block_on(async {
+    /// let chunks = stream::iter(vec!["GET /\nPOST /a\nGET /b\n"]).map(Ok::<_, io::Error>);
+    /// let mut posts = 0;
+    /// stream_lines::bytes(chunks)
+    ///     .for_each_line(|line| {
+    ///         if line.starts_with(b"POST ") {
+    ///             posts += 1;
+    ///         }
+    ///     })
+    ///     .await
+    ///     .unwrap();
+    /// assert_eq!(posts, 1);
+    /// # })
+    /// ```
+    pub async fn for_each_line<C, SE, G>(
+        self,
+        mut f: G,
+    ) -> Result<(), SE>
+    where
+        S: Stream<Item = Result<C, SE>>,
+        C: AsRef<[u8]>,
+        L: Limit<SE>,
+        G: FnMut(&[u8]),
+    {
+        let mut lines = pin!(self);
+        while let Some(line) = poll_fn(|cx| {
+            lines
+                .as_mut()
+                .poll_line_ref(cx)
+                .map(|line| line.map(|line| line.map(&mut f)))
+        })
+        .await
+        {
+            line?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,189 @@
+use std::ops::Range;
+
+use bytes::Bytes;
+use memchr::{memchr, memchr2, memmem};
+
+use crate::{CR, LF};
+
+/// The pattern a stream's lines are delimited by
+///
+/// Single bytes convert into `Delimiter::Byte` and anything longer, like `"\r\n\r\n"`
+/// or `"--boundary--"`, into `Delimiter::Sequence`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Delimiter {
+    /// A single byte, such as LF (\n) or NUL (\0)
+    Byte(u8),
+    /// A sequence of bytes, which may be split across the chunks a stream yields
+    Sequence(Bytes),
+    /// Universal newlines: any of LF (\n), CRLF (\r\n) or a lone CR (\r), as
+    /// used by classic Mac files and the progress bars of tools like `curl`
+    ///
+    /// A CR at the end of one chunk is not taken as a lone CR until the next is
+    /// read, so that a CRLF split between them yields a single line
+    Universal,
+    /// The mandatory line breaks of the Unicode line breaking algorithm
+    /// ([UAX #14](https://www.unicode.org/reports/tr14/)): universal newlines along with
+    /// VT (\u{b}), FF (\u{c}), NEL (\u{85}), LINE SEPARATOR (\u{2028}) and
+    /// PARAGRAPH SEPARATOR (\u{2029}), recognized in UTF-8 even when split across chunks
+    Unicode,
+}
+
+impl Delimiter {
+    /// Finds the first delimiter in `buffer` that starts at or after `from`. When there
+    /// isn't one, returns where the next search should start from, as the end of
+    /// `buffer` may be the start of a delimiter that has not been read yet, unless
+    /// `eof` says there is nothing left to read.
+    pub(crate) fn find(
+        &self,
+        buffer: &[u8],
+        from: usize,
+        eof: bool,
+    ) -> Result<Range<usize>, usize> {
+        match self {
+            Delimiter::Byte(byte) => match memchr(*byte, &buffer[from..]) {
+                Some(offset) => Ok(from + offset..from + offset + 1),
+                None => Err(buffer.len()),
+            },
+            Delimiter::Sequence(sequence) => match memmem::find(&buffer[from..], sequence) {
+                Some(offset) => Ok(from + offset..from + offset + sequence.len()),
+                None => Err(from.max((buffer.len() + 1).saturating_sub(sequence.len()))),
+            },
+            Delimiter::Universal => match memchr2(LF, CR, &buffer[from..]) {
+                Some(offset) => {
+                    let at = from + offset;
+                    match buffer.get(at + 1) {
+                        _ if buffer[at] == LF => Ok(at..at + 1),
+                        Some(&LF) => Ok(at..at + 2),
+                        Some(_) => Ok(at..at + 1),
+                        None if eof => Ok(at..at + 1),
+                        None => Err(at),
+                    }
+                }
+                None => Err(buffer.len()),
+            },
+            Delimiter::Unicode => find_unicode(buffer, from, eof),
+        }
+    }
+}
+
+fn find_unicode(
+    buffer: &[u8],
+    mut from: usize,
+    eof: bool,
+) -> Result<Range<usize>, usize> {
+    // the bytes that may start a mandatory break in UTF-8
+    while let Some(offset) = buffer[from..]
+        .iter()
+        .position(|b| matches!(b, b'\n' | b'\x0b' | b'\x0c' | b'\r' | 0xc2 | 0xe2))
+    {
+        let at = from + offset;
+        let rest = &buffer[at + 1..];
+        let len = match (buffer[at], rest) {
+            (CR, [LF, ..]) => 2,
+            (CR, []) if !eof => return Err(at),
+            (0xc2, [0x85, ..]) => 2,
+            (0xe2, [0x80, 0xa8 | 0xa9, ..]) => 3,
+            (0xc2, []) | (0xe2, []) | (0xe2, [0x80]) if !eof => return Err(at),
+            (0xc2, _) | (0xe2, _) => {
+                from = at + 1;
+                continue;
+            }
+            _ => 1,
+        };
+        return Ok(at..at + len);
+    }
+    Err(buffer.len())
+}
+
+impl From<u8> for Delimiter {
+    fn from(byte: u8) -> Self {
+        Delimiter::Byte(byte)
+    }
+}
+
+impl From<Bytes> for Delimiter {
+    fn from(sequence: Bytes) -> Self {
+        assert!(!sequence.is_empty(), "delimiters may not be empty");
+        match *sequence {
+            [byte] => Delimiter::Byte(byte),
+            _ => Delimiter::Sequence(sequence),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Delimiter {
+    fn from(sequence: Vec<u8>) -> Self {
+        Bytes::from(sequence).into()
+    }
+}
+
+impl From<&'static [u8]> for Delimiter {
+    fn from(sequence: &'static [u8]) -> Self {
+        Bytes::from_static(sequence).into()
+    }
+}
+
+impl From<&'static str> for Delimiter {
+    fn from(sequence: &'static str) -> Self {
+        sequence.as_bytes().into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_bytes() {
+        let lf = Delimiter::from(b'\n');
+        assert_eq!(lf.find(b"ab\ncd\n", 0, false), Ok(2..3));
+        assert_eq!(lf.find(b"ab\ncd\n", 3, false), Ok(5..6));
+        assert_eq!(lf.find(b"abcd", 1, false), Err(4));
+    }
+
+    #[test]
+    fn it_finds_sequences() {
+        let crlfcrlf = Delimiter::from("\r\n\r\n");
+        assert_eq!(crlfcrlf.find(b"a\r\nb\r\n\r\nc", 0, false), Ok(4..8));
+        assert_eq!(crlfcrlf.find(b"a\r\nb\r\n\r", 0, false), Err(4));
+        assert_eq!(crlfcrlf.find(b"a\r\nb\r\n\r", 5, false), Err(5));
+        assert_eq!(crlfcrlf.find(b"ab", 0, false), Err(0));
+    }
+
+    #[test]
+    fn it_finds_universal_newlines() {
+        let universal = Delimiter::Universal;
+        assert_eq!(universal.find(b"ab\ncd", 0, false), Ok(2..3));
+        assert_eq!(universal.find(b"ab\r\ncd", 0, false), Ok(2..4));
+        assert_eq!(universal.find(b"ab\rcd", 0, false), Ok(2..3));
+        assert_eq!(universal.find(b"ab\r", 0, false), Err(2));
+        assert_eq!(universal.find(b"ab\r", 0, true), Ok(2..3));
+        assert_eq!(universal.find(b"abcd", 0, false), Err(4));
+    }
+
+    #[test]
+    fn it_finds_unicode_line_breaks() {
+        let unicode = Delimiter::Unicode;
+        assert_eq!(unicode.find(b"ab\r\ncd", 0, false), Ok(2..4));
+        assert_eq!(unicode.find(b"ab\x0ccd", 0, false), Ok(2..3));
+        assert_eq!(unicode.find("ab\u{85}cd".as_bytes(), 0, false), Ok(2..4));
+        assert_eq!(unicode.find("ab\u{2028}cd".as_bytes(), 0, false), Ok(2..5));
+        assert_eq!(unicode.find("ab\u{2029}".as_bytes(), 0, false), Ok(2..5));
+        assert_eq!(unicode.find(b"ab\xe2\x80", 0, false), Err(2));
+        assert_eq!(unicode.find(b"ab\xe2\x80", 0, true), Err(4));
+        assert_eq!(unicode.find(b"ab\xc2", 0, false), Err(2));
+        assert_eq!(
+            unicode.find("\u{a9}\u{2030}\u{a0}".as_bytes(), 0, false),
+            Err(7)
+        );
+    }
+
+    #[test]
+    fn it_converts_single_bytes_to_bytes() {
+        assert_eq!(Delimiter::from(";"), Delimiter::Byte(b';'));
+        assert_eq!(
+            Delimiter::from(vec![b'\n', b'\n']),
+            Delimiter::Sequence(Bytes::from_static(b"\n\n"))
+        );
+    }
+}
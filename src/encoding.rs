@@ -0,0 +1,188 @@
+//! Lines of text in encodings other than UTF-8, such as Windows-1252, Shift_JIS or
+//! UTF-16, decoded with [encoding_rs](https://docs.rs/encoding_rs)
+//!
+//! Chunks are decoded into UTF-8 before they are split into lines, so characters split
+//! across chunks are reassembled and newlines encoded in more than one byte, as in
+//! UTF-16, are found like any other. A byte order mark at the start of a stream
+//! selects UTF-8, UTF-16LE or UTF-16BE in place of the given encoding, and is removed.
+//!
+//! ```
+//! use std::io;
+//!
+//! use futures::{stream, StreamExt};
+//! use stream_lines::encoding;
+//!
+//! # futures::executor::block_on(async {
+//! let chunks = stream::iter(vec![&b"\xff\xfeo\0n\0"[..], b"e\0\n\0t\0w\0o\0"]);
+//! let lines = encoding::strings(chunks.map(Ok::<_, io::Error>), encoding::WINDOWS_1252)
+//!     .collect::<Vec<_>>()
+//!     .await;
+//! assert_eq!(lines.len(), 2);
+//! assert_eq!(lines[0].as_ref().unwrap(), "one");
+//! assert_eq!(lines[1].as_ref().unwrap(), "two");
+//! # })
+//! ```
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use encoding_rs::Decoder;
+use futures_core::{ready, Stream};
+use pin_project_lite::pin_project;
+
+use crate::{builder, Lines};
+
+pub use encoding_rs::{
+    Encoding, BIG5, EUC_JP, EUC_KR, GBK, ISO_8859_15, ISO_8859_2, SHIFT_JIS, UTF_16BE, UTF_16LE,
+    UTF_8, WINDOWS_1251, WINDOWS_1252,
+};
+
+/// A lined oriented stream of `Strings` decoded from `encoding`, or from the encoding
+/// a leading byte order mark names. Malformed sequences are replaced with U+FFFD.
+pub fn strings<S, C, E>(
+    s: S,
+    encoding: &'static Encoding,
+) -> Lines<Decoded<S>, String, E>
+where
+    S: Stream<Item = Result<C, E>>,
+    C: AsRef<[u8]>,
+{
+    builder().strings_lossy(decode(s, encoding))
+}
+
+/// Decodes a stream of chunks in `encoding`, or the encoding a leading byte order
+/// mark names, into chunks of UTF-8 that may be handed to `builder()`
+pub fn decode<S>(
+    s: S,
+    encoding: &'static Encoding,
+) -> Decoded<S> {
+    Decoded {
+        stream: s,
+        decoder: encoding.new_decoder(),
+        done: false,
+    }
+}
+
+pin_project! {
+    /// Stream returned by `decode`
+    pub struct Decoded<S> {
+        #[pin]
+        stream: S,
+        decoder: Decoder,
+        done: bool,
+    }
+}
+
+impl<S> Decoded<S> {
+    /// The encoding being decoded, which is only settled by a byte order mark
+    /// once the first few bytes are read
+    pub fn encoding(&self) -> &'static Encoding {
+        self.decoder.encoding()
+    }
+}
+
+impl<S, C, E> Stream for Decoded<S>
+where
+    S: Stream<Item = Result<C, E>>,
+    C: AsRef<[u8]>,
+{
+    type Item = Result<Bytes, E>;
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        while !*this.done {
+            let decoded = match ready!(this.stream.as_mut().poll_next(cx)) {
+                Some(Ok(chunk)) => decode_chunk(this.decoder, chunk.as_ref(), false),
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => {
+                    *this.done = true;
+                    decode_chunk(this.decoder, &[], true)
+                }
+            };
+            // chunks holding only part of a character or a byte order mark decode to nothing
+            if !decoded.is_empty() {
+                return Poll::Ready(Some(Ok(decoded)));
+            }
+        }
+        Poll::Ready(None)
+    }
+}
+
+fn decode_chunk(
+    decoder: &mut Decoder,
+    chunk: &[u8],
+    last: bool,
+) -> Bytes {
+    let capacity = decoder
+        .max_utf8_buffer_length(chunk.len())
+        .expect("chunk too large to decode");
+    let mut decoded = String::with_capacity(capacity);
+    // with room for the worst case, the whole chunk is always decoded
+    let _ = decoder.decode_to_string(chunk, &mut decoded, last);
+    decoded.into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{stream, StreamExt};
+    use std::io;
+
+    async fn decoded_lines(
+        chunks: Vec<&'static [u8]>,
+        encoding: &'static Encoding,
+    ) -> Vec<String> {
+        strings(stream::iter(chunks).map(Ok::<_, io::Error>), encoding)
+            .map(Result::unwrap)
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn it_decodes_single_byte_encodings() {
+        assert_eq!(
+            decoded_lines(vec![b"caf\xe9\r\n\x80", b"5\n"], WINDOWS_1252).await,
+            vec!["caf\u{e9}", "\u{20ac}5"]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_decodes_characters_split_across_chunks() {
+        // "日本\n語" in Shift_JIS
+        let bytes = b"\x93\xfa\x96\x7b\n\x8c\xea";
+        for at in 0..bytes.len() {
+            assert_eq!(
+                decoded_lines(vec![&bytes[..at], &bytes[at..]], SHIFT_JIS).await,
+                vec!["\u{65e5}\u{672c}", "\u{8a9e}"]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_decodes_utf16_newlines_split_across_chunks() {
+        let bytes = b"a\0\r\0\n\0b\0";
+        for at in 0..bytes.len() {
+            assert_eq!(
+                decoded_lines(vec![&bytes[..at], &bytes[at..]], UTF_16LE).await,
+                vec!["a", "b"]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_selects_encodings_by_byte_order_mark() {
+        assert_eq!(
+            decoded_lines(vec![b"\xfe", b"\xff\0a\0\n\0b"], WINDOWS_1252).await,
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            decoded_lines(vec![b"\xef\xbb", b"\xbfcaf\xc3\xa9"], WINDOWS_1252).await,
+            vec!["caf\u{e9}"]
+        );
+        let mut decoded = decode(stream::iter(vec![Ok::<_, ()>(&b"\xff\xfea\0"[..])]), UTF_8);
+        assert_eq!(decoded.next().await, Some(Ok(Bytes::from("a"))));
+        assert_eq!(decoded.encoding(), UTF_16LE);
+    }
+}
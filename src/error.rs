@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_core::{ready, Stream};
+use pin_project_lite::pin_project;
+
+use crate::{Limit, Lines, Position, Raw, Unlimited};
+
+/// The most bytes of a line a `LineError` keeps
+const PREVIEW: usize = 64;
+
+/// A line's conversion error along with where the line was read from and what it
+/// started with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineError<E> {
+    position: Position,
+    preview: Bytes,
+    truncated: bool,
+    source: E,
+}
+
+impl<E> LineError<E> {
+    /// The 1-based number of the line that failed to convert
+    pub fn line(&self) -> u64 {
+        self.position.line
+    }
+
+    /// The offset of the line's first byte in the stream
+    pub fn offset(&self) -> u64 {
+        self.position.start
+    }
+
+    /// Where the line that failed to convert was read from
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Up to the first 64 bytes of the line that failed to convert
+    pub fn preview(&self) -> &[u8] {
+        &self.preview
+    }
+
+    /// The error the line's converter failed with
+    pub fn get_ref(&self) -> &E {
+        &self.source
+    }
+
+    /// Unwraps the error the line's converter failed with
+    pub fn into_inner(self) -> E {
+        self.source
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for LineError<E> {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "line {}, byte {}: {} in \"{}{}\"",
+            self.position.line,
+            self.position.start,
+            self.source,
+            self.preview.escape_ascii(),
+            if self.truncated { "..." } else { "" }
+        )
+    }
+}
+
+impl<E: Error + 'static> Error for LineError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl<E: Error + Send + Sync + 'static> From<LineError<E>> for io::Error {
+    fn from(err: LineError<E>) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+pin_project! {
+    /// Stream returned by `Lines::with_error_context`
+    pub struct Contextual<S, O, E, F, L = Unlimited> {
+        #[pin]
+        lines: Lines<S, O, E, F, L>,
+    }
+}
+
+impl<S, O, E, F, L> Lines<S, O, E, F, L> {
+    /// Wraps conversion errors in a `LineError`, which the stream's error type
+    /// converts from in place of the converter's own error type
+    ///
+    /// Only the start of each line is copied aside in case its conversion fails, so
+    /// lines that convert are not allocated for.
+    ///
+    /// ```
+    /// use std::io;
+    ///
+    /// use futures::{stream, StreamExt};
+    ///
+    /// # futures::executor::block_on(async {
+    /// let chunks = stream::iter(vec![&b"ok\n"[..], b"caf\xff\n"]).map(Ok::<_, io::Error>);
+    /// let mut lines = stream_lines::strings(chunks).with_error_context();
+    /// assert_eq!(lines.next().await.unwrap().unwrap(), "ok");
+    /// assert_eq!(
+    ///     lines.next().await.unwrap().unwrap_err().to_string(),
+    ///     "line 2, byte 3: invalid utf-8 sequence of 1 bytes from index 3 in \"caf\\xff\""
+    /// );
+    /// # })
+    /// ```
+    pub fn with_error_context(self) -> Contextual<S, O, E, F, L> {
+        Contextual { lines: self }
+    }
+}
+
+impl<S, O, E, F, L> Lines<S, O, E, F, L> {
+    /// Converts a line polled with `poll_raw`, wrapping any error in a `LineError`
+    pub(crate) fn convert_in_context(
+        self: Pin<&mut Self>,
+        raw: Raw,
+    ) -> Result<O, LineError<E>>
+    where
+        F: FnMut(Bytes) -> Result<O, E>,
+    {
+        let len = raw.line.len().min(PREVIEW);
+        let mut preview = [0; PREVIEW];
+        preview[..len].copy_from_slice(&raw.line[..len]);
+        let truncated = raw.line.len() > len;
+        self.convert(raw.line).map_err(|source| LineError {
+            position: raw.position,
+            preview: Bytes::copy_from_slice(&preview[..len]),
+            truncated,
+            source,
+        })
+    }
+}
+
+impl<S, C, SE, O, E, F, L> Stream for Contextual<S, O, E, F, L>
+where
+    S: Stream<Item = Result<C, SE>>,
+    C: AsRef<[u8]>,
+    SE: From<LineError<E>>,
+    F: FnMut(Bytes) -> Result<O, E>,
+    L: Limit<SE>,
+{
+    type Item = Result<O, SE>;
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut lines = self.project().lines;
+        Poll::Ready(match ready!(lines.as_mut().poll_raw(cx)) {
+            Some(Ok(raw)) => Some(lines.convert_in_context(raw).map_err(SE::from)),
+            Some(Err(err)) => Some(Err(err)),
+            None => None,
+        })
+    }
+}
@@ -0,0 +1,346 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_core::{ready, Stream};
+use pin_project_lite::pin_project;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{builder, Convert, Limit, LineError, Lines, LinesBuilder, Unlimited, LF};
+
+/// The size chunks of encoded JSON Lines are filled up to by default
+const BATCH_SIZE: usize = 8 * 1024;
+
+/// A stream of values deserialized from each line of JSON a stream of bytes is made
+/// up of, skipping blank lines and any leading UTF-8 byte order mark
+///
+/// Lines are deserialized from the bytes they were read into, without first being
+/// copied into a `String`. Lines that fail to deserialize are yielded as a `LineError`
+/// telling which line it was, which the stream's error type converts from.
+///
+/// ```
+/// use std::io;
+///
+/// use futures::{stream, StreamExt, TryStreamExt};
+/// use serde::Deserialize;
+///
+/// #[derive(Debug, Deserialize, PartialEq)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// # futures::executor::block_on(async {
+/// let chunks = stream::iter(vec!["{\"x\": 1, \"y\": 2}\n\n{\"x\"", ": 3, \"y\": 4}\n"]);
+/// let points = stream_lines::json_lines::<Point, _>(chunks.map(Ok::<_, io::Error>))
+///     .try_collect::<Vec<_>>()
+///     .await
+///     .unwrap();
+/// assert_eq!(points, vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }]);
+/// # })
+/// ```
+pub fn json_lines<T, S>(s: S) -> JsonLines<S, T>
+where
+    S: Stream,
+    T: DeserializeOwned,
+{
+    builder().strip_bom(true).json_lines(s)
+}
+
+impl<L> LinesBuilder<L> {
+    /// Creates a stream of values deserialized from lines of JSON. See
+    /// [json_lines](fn.json_lines.html)
+    pub fn json_lines<T, S>(
+        self,
+        stream: S,
+    ) -> JsonLines<S, T, L>
+    where
+        S: Stream,
+        T: DeserializeOwned,
+    {
+        JsonLines {
+            lines: self.build(stream, from_json as fn(_) -> _),
+        }
+    }
+}
+
+pin_project! {
+    /// Stream returned by `json_lines`
+    pub struct JsonLines<S, T, L = Unlimited> {
+        #[pin]
+        lines: Lines<S, T, serde_json::Error, Convert<T, serde_json::Error>, L>,
+    }
+}
+
+impl<S, C, SE, T, L> Stream for JsonLines<S, T, L>
+where
+    S: Stream<Item = Result<C, SE>>,
+    C: AsRef<[u8]>,
+    SE: From<LineError<serde_json::Error>>,
+    T: DeserializeOwned,
+    L: Limit<SE>,
+{
+    type Item = Result<T, SE>;
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut lines = self.project().lines;
+        loop {
+            return Poll::Ready(match ready!(lines.as_mut().poll_raw(cx)) {
+                Some(Ok(raw)) if is_blank(&raw.line) => continue,
+                Some(Ok(raw)) => Some(lines.as_mut().convert_in_context(raw).map_err(SE::from)),
+                Some(Err(err)) => Some(Err(err)),
+                None => None,
+            });
+        }
+    }
+}
+
+/// Encodes a stream of values into chunks of JSON Lines, as may be sent in the body of a
+/// response, as with [sse::encode](sse/fn.encode.html)
+///
+/// Each value is written as compact JSON on a line of its own, as JSON escapes any
+/// newlines in strings. Values that are ready one after another are written to the
+/// same chunk, until it is `batch_size` bytes long, saving on writes. A value that
+/// fails to serialize is yielded as an error in its place, after the values before it.
+///
+/// ```
+/// use futures::{stream, StreamExt};
+///
+/// # futures::executor::block_on(async {
+/// let values = stream::iter(vec![vec!["a"], vec!["b\nc", "d"]]);
+/// let mut chunks = stream_lines::encode_json_lines(values);
+/// assert_eq!(
+///     chunks.next().await.unwrap().unwrap(),
+///     "[\"a\"]\n[\"b\\nc\",\"d\"]\n"
+/// );
+/// # })
+/// ```
+pub fn encode_json_lines<S>(s: S) -> EncodeJsonLines<S>
+where
+    S: Stream,
+    S::Item: Serialize,
+{
+    EncodeJsonLines {
+        stream: s,
+        buf: BytesMut::new(),
+        batch_size: BATCH_SIZE,
+        error: None,
+        done: false,
+    }
+}
+
+pin_project! {
+    /// Stream returned by `encode_json_lines`
+    pub struct EncodeJsonLines<S> {
+        #[pin]
+        stream: S,
+        buf: BytesMut,
+        batch_size: usize,
+        // the error of a value that failed to serialize after those before it were
+        // buffered, to yield once they have been
+        error: Option<serde_json::Error>,
+        done: bool,
+    }
+}
+
+impl<S> EncodeJsonLines<S> {
+    /// Sets the size chunks are filled up to with values that are ready one after
+    /// another. Defaults to 8 KiB. A chunk may go over when a value does, and a size
+    /// of 0 yields every value in a chunk of its own
+    pub fn batch_size(
+        mut self,
+        batch_size: usize,
+    ) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+}
+
+impl<S> Stream for EncodeJsonLines<S>
+where
+    S: Stream,
+    S::Item: Serialize,
+{
+    type Item = Result<Bytes, serde_json::Error>;
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        if let Some(err) = this.error.take() {
+            return Poll::Ready(Some(Err(err)));
+        }
+        while !*this.done && (this.buf.is_empty() || this.buf.len() < *this.batch_size) {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(value)) => {
+                    let len = this.buf.len();
+                    if let Err(err) = serde_json::to_writer((&mut *this.buf).writer(), &value) {
+                        this.buf.truncate(len);
+                        if this.buf.is_empty() {
+                            return Poll::Ready(Some(Err(err)));
+                        }
+                        *this.error = Some(err);
+                        break;
+                    }
+                    this.buf.put_u8(LF);
+                }
+                Poll::Ready(None) => *this.done = true,
+                Poll::Pending => break,
+            }
+        }
+        if !this.buf.is_empty() {
+            Poll::Ready(Some(Ok(this.buf.split().freeze())))
+        } else if *this.done {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+fn from_json<T: DeserializeOwned>(line: Bytes) -> Result<T, serde_json::Error> {
+    serde_json::from_slice(&line)
+}
+
+fn is_blank(line: &[u8]) -> bool {
+    line.iter()
+        .all(|b| matches!(b, b' ' | b'\t' | b'\r' | b'\n'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{stream, StreamExt};
+    use futures_test::stream::StreamTestExt;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+    use std::io;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Record {
+        id: u32,
+        name: String,
+    }
+
+    fn record(
+        id: u32,
+        name: &str,
+    ) -> Record {
+        Record {
+            id,
+            name: name.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_deserializes_lines_split_across_chunks() {
+        let records = json_lines::<Record, _>(
+            stream::iter(vec![
+                "{\"id\": 1, \"name\": \"a\"}\r\n{\"id\"",
+                ": 2, \"name\": \"b\\nc\"}\n",
+                "{\"id\": 3, \"name\": \"d\"}",
+            ])
+            .map(Ok::<_, io::Error>),
+        )
+        .map(Result::unwrap)
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(
+            records,
+            vec![record(1, "a"), record(2, "b\nc"), record(3, "d")]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_strips_a_byte_order_mark() {
+        let values =
+            json_lines::<u32, _>(stream::iter(vec!["\u{feff}1\r\n2\n"]).map(Ok::<_, io::Error>))
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+                .await;
+        assert_eq!(values, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn it_skips_blank_lines() {
+        let records = json_lines::<Record, _>(
+            stream::iter(vec!["\n  \n{\"id\": 1, \"name\": \"a\"}\n\t\r\n\n"])
+                .map(Ok::<_, io::Error>),
+        )
+        .map(Result::unwrap)
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(records, vec![record(1, "a")]);
+    }
+
+    #[tokio::test]
+    async fn it_reports_the_line_that_failed() {
+        #[derive(Debug)]
+        enum Err {
+            Json(LineError<serde_json::Error>),
+        }
+        impl From<LineError<serde_json::Error>> for Err {
+            fn from(e: LineError<serde_json::Error>) -> Self {
+                Err::Json(e)
+            }
+        }
+        let mut records = json_lines::<Record, _>(
+            stream::iter(vec!["{\"id\": 1, \"name\": \"a\"}\n\n{\"id\": \"2\"}\n"])
+                .map(Ok::<_, Err>),
+        );
+        assert_eq!(records.next().await.unwrap().unwrap(), record(1, "a"));
+        match records.next().await {
+            Some(Err(Err::Json(err))) => {
+                assert_eq!((err.line(), err.offset()), (3, 24));
+                assert!(err.get_ref().is_data());
+                assert_eq!(err.preview(), b"{\"id\": \"2\"}");
+            }
+            other => panic!("expected a json error, got {:?}", other),
+        }
+        assert!(records.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn it_batches_values_that_are_ready() {
+        let values = || stream::iter(vec!["a\nb", "c", "d", "e"]);
+        let chunks = encode_json_lines(values())
+            .batch_size(8)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(chunks, vec!["\"a\\nb\"\n\"c\"\n", "\"d\"\n\"e\"\n"]);
+        let chunks = encode_json_lines(values().interleave_pending())
+            .batch_size(8)
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(chunks, vec!["\"a\\nb\"\n", "\"c\"\n", "\"d\"\n", "\"e\"\n"]);
+    }
+
+    #[tokio::test]
+    async fn it_yields_values_before_those_that_fail_to_serialize() {
+        let valid = BTreeMap::new();
+        let invalid = BTreeMap::from([(vec![1], 2)]);
+        let chunks = encode_json_lines(stream::iter(vec![
+            valid.clone(),
+            invalid.clone(),
+            invalid,
+            valid,
+        ]))
+        .map(|chunk| chunk.map_err(|e| e.to_string()))
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(
+            chunks,
+            vec![
+                Ok(Bytes::from("{}\n")),
+                Err("key must be a string".into()),
+                Err("key must be a string".into()),
+                Ok(Bytes::from("{}\n"))
+            ]
+        );
+    }
+}
@@ -1,10 +1,10 @@
 //! Lined-oriented Rustlang
-//! [Streams](http://alexcrichton.com/futures-rs/futures/stream/index.html)
+//! [Streams](https://docs.rs/futures/0.3/futures/stream/index.html)
 //!
 //! Streams represent spools of results computed asyncronously. They are the async
 //! analog to Rustlang's Iterator type.
 //!
-//! This crate represents a Stream transformer over Stream's of `AsRef<[u8]>`
+//! This crate represents a Stream transformer over Stream's of `Result`s of `AsRef<[u8]>`
 //! that result in Stream's of line-oriented values. Chunks of bytes are delimted
 //! by LF (\n) and optionally CRLF (\r\n) patterns.
 //!
@@ -18,20 +18,17 @@
 //!  The underlying abstraction is the [Lines](struct.Lines.html) type which
 //!   return's a stream over an arbitrarily converted type, but most
 //!  applications will typically want to use the `strings` method which return's
-//!  a Stream of Strings
+//!  a Stream of Strings. Lines are split off of an internal buffer in place and
+//!  handed to converters as [Bytes](struct.Bytes.html), so the `bytes` method which
+//!  return's a Stream of the raw lines does not copy them at all.
 //!
 //! ```no_run
-//! extern crate futures;
-//! extern crate stream_lines;
-//! extern crate tokio;
-//!
 //! use std::string::FromUtf8Error;
 //!
-//! use futures::stream::iter_ok;
-//! use futures::Stream;
-//! use tokio::runtime::Runtime;
+//! use futures::{stream, StreamExt, TryStreamExt};
 //!
-//! fn main() {
+//! #[tokio::main]
+//! async fn main() {
 //!     let chunks = vec![
 //!        "\nhello ",
 //!         "world\n",
@@ -39,32 +36,161 @@
 //!         "what a\nlovely",
 //!         "\nday\n"
 //!      ];
-//!     let stream = iter_ok::<_, FromUtf8Error>(chunks);
-//!     let print =
-//!         stream_lines::strings(stream)
-//!            .for_each(|line| Ok(println!("{}", line)));
-//!     Runtime::new().expect("failed to initialize runtime").block_on(print).expect("failed to complete stream");
+//!     let stream = stream::iter(chunks).map(Ok::<_, FromUtf8Error>);
+//!     stream_lines::strings(stream)
+//!         .try_for_each(|line| async move { Ok(println!("{}", line)) })
+//!         .await
+//!         .expect("failed to complete stream");
 //! }
 //! ```
+//!
+//! # Features
+//!
+//! * `encoding` adds the [encoding](encoding/index.html) module, for lines of text
+//!   in encodings other than UTF-8
+//! * `serde` adds [json_lines](fn.json_lines.html) and
+//!   [encode_json_lines](fn.encode_json_lines.html), for reading and writing lines of JSON
 #![deny(missing_docs)]
 
-extern crate futures;
-
-use std::mem::replace;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
 use std::string::FromUtf8Error;
+use std::task::{Context, Poll};
+
+use bytes::{Buf, BytesMut};
+use futures_core::{ready, Stream};
+use pin_project_lite::pin_project;
+
+mod borrowed;
+mod delimiter;
+#[cfg(feature = "encoding")]
+pub mod encoding;
+mod error;
+#[cfg(feature = "serde")]
+mod json;
+mod line;
+mod lossy;
+mod position;
+mod recover;
+pub mod sse;
 
-use futures::stream::Fuse;
-use futures::{Async, Poll, Stream};
+pub use bytes::Bytes;
+pub use delimiter::Delimiter;
+pub use error::{Contextual, LineError};
+#[cfg(feature = "serde")]
+pub use json::{encode_json_lines, json_lines, EncodeJsonLines, JsonLines};
+pub use line::{Line, Terminated, Terminator};
+pub use lossy::Lossy;
+pub use position::{Position, Positioned};
+pub use recover::{Recovering, SkipErrors};
 
 const LF: u8 = b'\n';
 const CR: u8 = b'\r';
+const NUL: u8 = b'\0';
+/// The UTF-8 encoded byte order mark
+const BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// What to do with a line longer than a configured `max_line_length`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overflow {
+    /// Yield a `LineTooLong` error in place of the line
+    Error,
+    /// Yield the first `max_line_length` bytes of the line, discarding the rest
+    /// up to the next delimiter
+    Truncate,
+    /// Yield the line in `max_line_length` sized pieces, all but the last of which
+    /// are flagged as partial
+    ///
+    /// Pieces are cut at a byte count, so a multi-byte UTF-8 character may be split
+    /// between two of them, neither of which is then valid UTF-8, unless lines are
+    /// delimited by `Delimiter::Unicode`, which cuts pieces short between characters.
+    /// `Truncate` cuts lines the same way.
+    Split,
+}
+
+/// Error yielded in place of a line longer than a configured `max_line_length`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineTooLong {
+    limit: usize,
+}
+
+impl LineTooLong {
+    /// The maximum line length that was exceeded
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+}
+
+impl fmt::Display for LineTooLong {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter,
+    ) -> fmt::Result {
+        write!(
+            f,
+            "line exceeded the maximum length of {} bytes",
+            self.limit
+        )
+    }
+}
+
+impl Error for LineTooLong {}
+
+impl From<LineTooLong> for io::Error {
+    fn from(err: LineTooLong) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Marks a `Lines` stream without a `max_line_length`, which never yields a
+/// `LineTooLong` error, so its error type need not convert from one
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Unlimited;
 
-/// Converts a fused `Stream` of bytes into a line-oriented stream
-/// of a target type
-pub struct Lines<S: Stream, O, E> {
-    buffered: Option<Vec<u8>>,
-    stream: Fuse<S>,
-    into: fn(Vec<u8>) -> Result<O, E>,
+/// Marks a `Lines` stream with a `max_line_length`, whose error type must convert
+/// from the `LineTooLong` errors it may yield
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limited;
+
+/// How a `LineTooLong` error is converted into a stream's error type `SE`, which
+/// only streams with a `max_line_length` ever need to do
+pub trait Limit<SE> {
+    /// Converts `err` into the stream's error type
+    fn too_long(err: LineTooLong) -> SE;
+}
+
+impl<SE> Limit<SE> for Unlimited {
+    fn too_long(_: LineTooLong) -> SE {
+        unreachable!("lines are only too long once a max_line_length is set")
+    }
+}
+
+impl<SE: From<LineTooLong>> Limit<SE> for Limited {
+    fn too_long(err: LineTooLong) -> SE {
+        SE::from(err)
+    }
+}
+
+/// The converters the built in line types are made with
+type Convert<O, E> = fn(Bytes) -> Result<O, E>;
+
+pin_project! {
+    /// Converts a `Stream` of bytes into a line-oriented stream
+    /// of a target type. `L` is `Limited` once a `max_line_length` is set
+    pub struct Lines<S, O, E, F = fn(Bytes) -> Result<O, E>, L = Unlimited> {
+        buffer: Buffer,
+        #[pin]
+        stream: S,
+        done: bool,
+        // the line last lent out by `poll_line_ref`
+        lent: Option<Bytes>,
+        into: F,
+        output: PhantomData<fn() -> Result<O, E>>,
+        limited: PhantomData<L>,
+    }
 }
 
 /// A lined oriented stream of `Strings`
@@ -72,125 +198,1598 @@ pub fn strings<S>(s: S) -> Lines<S, String, FromUtf8Error>
 where
     S: Stream,
 {
-    Lines::new(s, String::from_utf8)
+    Lines::new(s, utf8)
+}
+
+/// A lined oriented stream of `Strings` that replaces invalid UTF-8 with U+FFFD
+/// rather than failing, so only errors from the underlying stream are yielded
+pub fn strings_lossy<S, C, E>(s: S) -> Lines<S, String, E>
+where
+    S: Stream<Item = Result<C, E>>,
+{
+    builder().strings_lossy(s)
+}
+
+/// A lined oriented stream of `Strings` that, in addition to LF, CRLF and lone CRs,
+/// also breaks lines on the Unicode line separators NEL (\u{85}), LINE SEPARATOR
+/// (\u{2028}) and PARAGRAPH SEPARATOR (\u{2029}), as well as VT and FF.
+/// See [Delimiter::Unicode](enum.Delimiter.html#variant.Unicode)
+pub fn unicode_strings<S>(s: S) -> Lines<S, String, FromUtf8Error>
+where
+    S: Stream,
+{
+    builder().delimiter(Delimiter::Unicode).strings(s)
+}
+
+/// A stream of `Strings` delimited by NUL (\0) bytes, as produced by `find -print0`
+/// and `xargs -0` style tools
+pub fn null_terminated<S>(s: S) -> Lines<S, String, FromUtf8Error>
+where
+    S: Stream,
+{
+    builder().delimiter(NUL).strings(s)
 }
 
-impl<S: Stream, O, E> Lines<S, O, E> {
+/// A lined oriented stream of `Bytes`, shared with the buffer they were read into
+pub fn bytes<S, C, E>(s: S) -> Lines<S, Bytes, E>
+where
+    S: Stream<Item = Result<C, E>>,
+{
+    Lines::new(s, Ok)
+}
+
+/// A lined oriented stream of values converted by a closure, which unlike the
+/// function `Lines::new` accepts may capture per stream context
+///
+/// ```
+/// use std::io;
+///
+/// use futures::{stream, StreamExt};
+///
+/// let prefix = String::from("> ");
+/// let chunks = stream::iter(vec!["hello\nworld\n"]).map(Ok::<_, io::Error>);
+/// let quoted = stream_lines::map_lines(chunks, move |line| {
+///     Ok::<_, io::Error>(format!("{}{}", prefix, String::from_utf8_lossy(&line)))
+/// });
+/// ```
+pub fn map_lines<S, O, E, F>(
+    s: S,
+    f: F,
+) -> Lines<S, O, E, F>
+where
+    F: FnMut(Bytes) -> Result<O, E>,
+{
+    builder().build(s, f)
+}
+
+/// Returns a `LinesBuilder` for configuring how streams are split into lines
+pub fn builder() -> LinesBuilder {
+    LinesBuilder::default()
+}
+
+fn utf8(line: Bytes) -> Result<String, FromUtf8Error> {
+    String::from_utf8(line.into())
+}
+
+/// Configures how a `Lines` stream splits up the bytes it reads
+///
+/// ```
+/// use std::io;
+///
+/// use futures::{stream, StreamExt};
+/// use stream_lines::Overflow;
+///
+/// let chunks = stream::iter(vec!["a;b", ";c"]).map(Ok::<_, io::Error>);
+/// let lines = stream_lines::builder()
+///     .delimiter(b';')
+///     .max_line_length(1024, Overflow::Error)
+///     .strings(chunks);
+/// ```
+#[derive(Clone, Debug)]
+pub struct LinesBuilder<L = Unlimited> {
+    delimiter: Delimiter,
+    strip_cr: bool,
+    keep_terminator: bool,
+    trailing_empty_line: bool,
+    strip_bom: bool,
+    limit: Option<(usize, Overflow)>,
+    capacity: usize,
+    limited: PhantomData<L>,
+}
+
+impl Default for LinesBuilder {
+    fn default() -> Self {
+        LinesBuilder {
+            delimiter: Delimiter::Byte(LF),
+            strip_cr: true,
+            keep_terminator: false,
+            trailing_empty_line: false,
+            strip_bom: false,
+            limit: None,
+            capacity: 0,
+            limited: PhantomData,
+        }
+    }
+}
+
+impl<L> LinesBuilder<L> {
+    /// Sets the byte, or sequence of bytes, lines end with. Defaults to LF (\n)
+    ///
+    /// # Panics
+    ///
+    /// When `delimiter` is an empty sequence
+    pub fn delimiter(
+        mut self,
+        delimiter: impl Into<Delimiter>,
+    ) -> Self {
+        self.delimiter = delimiter.into();
+        self
+    }
+
+    /// Sets whether a CR (\r) before an LF delimiter is removed along with it. Defaults to true.
+    /// CRs are never stripped from lines ending with any other delimiter
+    pub fn strip_cr(
+        mut self,
+        strip_cr: bool,
+    ) -> Self {
+        self.strip_cr = strip_cr;
+        self
+    }
+
+    /// Sets whether lines are yielded with their terminator left on the end. Defaults to false
+    pub fn keep_terminator(
+        mut self,
+        keep_terminator: bool,
+    ) -> Self {
+        self.keep_terminator = keep_terminator;
+        self
+    }
+
+    /// Sets whether the empty remainder of a stream that ends with a terminator is
+    /// yielded as one last, empty, line, as `Lines` did prior to 0.2. Defaults to false,
+    /// which like `BufRead::lines` yields `"a"` and `"b"`, but no `""`, for `"a\nb\n"`
+    pub fn trailing_empty_line(
+        mut self,
+        trailing_empty_line: bool,
+    ) -> Self {
+        self.trailing_empty_line = trailing_empty_line;
+        self
+    }
+
+    /// Sets whether a UTF-8 byte order mark (EF BB BF) at the start of the stream is
+    /// removed from the first line, as written by some Windows tools. Defaults to false.
+    /// Either way, [Lines::has_bom](struct.Lines.html#method.has_bom) reports whether
+    /// there was one
+    pub fn strip_bom(
+        mut self,
+        strip_bom: bool,
+    ) -> Self {
+        self.strip_bom = strip_bom;
+        self
+    }
+
+    /// Limits lines to `limit` bytes. See [Lines::max_line_length](struct.Lines.html#method.max_line_length)
+    ///
+    /// # Panics
+    ///
+    /// When `limit` is 0
+    pub fn max_line_length(
+        self,
+        limit: usize,
+        overflow: Overflow,
+    ) -> LinesBuilder<Limited> {
+        assert!(limit > 0, "max_line_length must be at least 1 byte");
+        LinesBuilder {
+            delimiter: self.delimiter,
+            strip_cr: self.strip_cr,
+            keep_terminator: self.keep_terminator,
+            trailing_empty_line: self.trailing_empty_line,
+            strip_bom: self.strip_bom,
+            limit: Some((limit, overflow)),
+            capacity: self.capacity,
+            limited: PhantomData,
+        }
+    }
+
+    /// Sets the number of bytes the buffer lines are read into initially has room for.
+    /// Defaults to 0, growing with the first chunk read
+    pub fn capacity(
+        mut self,
+        capacity: usize,
+    ) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Creates a `Lines` instance that wraps `stream`, converting lines with `into`
+    pub fn build<S, O, E, F>(
+        self,
+        stream: S,
+        into: F,
+    ) -> Lines<S, O, E, F, L>
+    where
+        F: FnMut(Bytes) -> Result<O, E>,
+    {
+        Lines {
+            buffer: Buffer {
+                bytes: None,
+                scanned: 0,
+                delimiter: self.delimiter,
+                strip_cr: self.strip_cr,
+                keep_terminator: self.keep_terminator,
+                trailing_empty_line: self.trailing_empty_line,
+                strip_bom: self.strip_bom,
+                bom: None,
+                limit: self.limit,
+                capacity: self.capacity,
+                discarding: false,
+                partial: false,
+                offset: 0,
+                line: 1,
+            },
+            stream,
+            done: false,
+            lent: None,
+            into,
+            output: PhantomData,
+            limited: PhantomData,
+        }
+    }
+
+    /// Creates a lined oriented stream of `Strings`
+    pub fn strings<S>(
+        self,
+        stream: S,
+    ) -> Lines<S, String, FromUtf8Error, Convert<String, FromUtf8Error>, L>
+    where
+        S: Stream,
+    {
+        self.build(stream, utf8 as fn(_) -> _)
+    }
+
+    /// Creates a lined oriented stream of `Strings`, replacing invalid UTF-8 with U+FFFD
+    pub fn strings_lossy<S, C, E>(
+        self,
+        stream: S,
+    ) -> Lines<S, String, E, Convert<String, E>, L>
+    where
+        S: Stream<Item = Result<C, E>>,
+    {
+        self.build(stream, lossy::lossy as fn(_) -> _)
+    }
+
+    /// Creates a lined oriented stream of `Lossy` strings, which count how many invalid
+    /// UTF-8 sequences were replaced in each line
+    pub fn strings_lossy_counted<S, C, E>(
+        self,
+        stream: S,
+    ) -> Lines<S, Lossy, E, Convert<Lossy, E>, L>
+    where
+        S: Stream<Item = Result<C, E>>,
+    {
+        self.build(stream, lossy::lossy_counted as fn(_) -> _)
+    }
+
+    /// Creates a lined oriented stream of `Bytes`
+    pub fn bytes<S, C, E>(
+        self,
+        stream: S,
+    ) -> Lines<S, Bytes, E, Convert<Bytes, E>, L>
+    where
+        S: Stream<Item = Result<C, E>>,
+    {
+        self.build(stream, Ok as fn(_) -> _)
+    }
+}
+
+impl<S, O, E> Lines<S, O, E> {
     /// Creates a new `Lines` instance that wraps another stream
     pub fn new(
         stream: S,
-        into: fn(Vec<u8>) -> Result<O, E>,
+        into: fn(Bytes) -> Result<O, E>,
     ) -> Self {
+        builder().build(stream, into)
+    }
+
+    /// Creates a new `Lines` instance that wraps another stream, splitting
+    /// lines on `delimiter` rather than LF. See [Delimiter](enum.Delimiter.html)
+    pub fn with_delimiter(
+        stream: S,
+        delimiter: impl Into<Delimiter>,
+        into: fn(Bytes) -> Result<O, E>,
+    ) -> Self {
+        builder().delimiter(delimiter).build(stream, into)
+    }
+}
+
+impl<S, O, E, F, L> Lines<S, O, E, F, L> {
+    /// Limits lines to `limit` bytes, not counting their delimiter, so that a peer
+    /// which never sends one can not grow the buffer without bound. Longer lines
+    /// are handled according to `overflow`, and the stream's error type must then
+    /// convert from `LineTooLong`.
+    ///
+    /// # Panics
+    ///
+    /// When `limit` is 0
+    pub fn max_line_length(
+        self,
+        limit: usize,
+        overflow: Overflow,
+    ) -> Lines<S, O, E, F, Limited> {
+        assert!(limit > 0, "max_line_length must be at least 1 byte");
+        self.with_limit(Some((limit, overflow)))
+    }
+
+    /// Replaces the limit lines are held to, along with the marker for whether the
+    /// stream may yield `LineTooLong` errors
+    fn with_limit<M>(
+        mut self,
+        limit: Option<(usize, Overflow)>,
+    ) -> Lines<S, O, E, F, M> {
+        self.buffer.limit = limit;
         Lines {
-            buffered: None,
-            stream: stream.fuse(),
-            into,
+            buffer: self.buffer,
+            stream: self.stream,
+            done: self.done,
+            lent: self.lent,
+            into: self.into,
+            output: PhantomData,
+            limited: PhantomData,
         }
     }
 
+    /// Returns true when the last item yielded was a piece of a line longer than
+    /// `max_line_length`, split with `Overflow::Split`, with more of the line to follow
+    pub fn is_partial(&self) -> bool {
+        self.buffer.partial
+    }
+
+    /// Returns true when the stream started with a UTF-8 byte order mark, which is
+    /// only known once the first line has been yielded
+    pub fn has_bom(&self) -> bool {
+        self.buffer.bom == Some(true)
+    }
+}
+
+/// A line split off of a `Buffer`, before it is converted
+struct Raw {
+    line: Bytes,
+    terminator: Option<Bytes>,
+    position: Position,
+}
+
+/// The bytes read from a stream that have not yet been yielded as lines
+struct Buffer {
+    bytes: Option<BytesMut>,
+    /// how much of `bytes` is known not to contain the start of a delimiter, so that
+    /// each byte is only searched once no matter how it was chunked
+    scanned: usize,
+    delimiter: Delimiter,
+    strip_cr: bool,
+    keep_terminator: bool,
+    trailing_empty_line: bool,
+    strip_bom: bool,
+    /// whether the stream starts with a byte order mark, once enough is read to tell
+    bom: Option<bool>,
+    limit: Option<(usize, Overflow)>,
+    capacity: usize,
+    /// true while skipping the rest of a line that overflowed `limit`
+    discarding: bool,
+    partial: bool,
+    /// where in the stream the buffer starts
+    offset: u64,
+    /// the number of the line at the front of the buffer
+    line: u64,
+}
+
+impl Buffer {
+    fn extend(
+        &mut self,
+        chunk: &[u8],
+    ) {
+        let capacity = self.capacity;
+        self.bytes
+            .get_or_insert_with(|| BytesMut::with_capacity(capacity))
+            .extend_from_slice(chunk)
+    }
+
+    /// Splits the next line off of the front of the buffer in place, separating its
+    /// terminator. When `flush` is true, whatever remains is treated as the last line.
     fn next(
         &mut self,
         flush: bool,
-    ) -> Option<Result<O, E>> {
-        let buffered = replace(&mut self.buffered, None);
-        if let Some(ref buffer) = buffered {
-            let mut split = buffer.splitn(2, |c| *c == LF);
-            if let Some(first) = split.next() {
-                let mut line = first.to_vec();
-                if let Some(&CR) = line.last() {
-                    line.pop();
+    ) -> Option<Result<Raw, LineTooLong>> {
+        if self.bom.is_none() && !self.detect_bom(flush) {
+            return None;
+        }
+        loop {
+            let buffer = self.bytes.as_mut()?;
+            let strip_cr = self.strip_cr && self.delimiter == Delimiter::Byte(LF);
+            // the end of the line's content and the end of its terminator
+            let (end, consumed, last) = match self.delimiter.find(buffer, self.scanned, flush) {
+                Ok(delimiter) => {
+                    self.scanned = delimiter.start;
+                    (
+                        content_end(buffer, delimiter.start, strip_cr),
+                        delimiter.end,
+                        false,
+                    )
+                }
+                Err(_) if flush && buffer.is_empty() && !self.trailing_empty_line => {
+                    self.bytes = None;
+                    return None;
                 }
-                if let Some(second) = split.next() {
-                    replace(&mut self.buffered, Some(second.to_vec()));
-                    return Some((self.into)(line));
-                } else if flush {
-                    return Some((self.into)(line));
+                Err(_) if flush => {
+                    self.scanned = buffer.len();
+                    (
+                        content_end(buffer, buffer.len(), strip_cr),
+                        buffer.len(),
+                        true,
+                    )
+                }
+                Err(resume) if self.discarding => {
+                    // keep only what may be the start of the next delimiter
+                    self.scanned = resume;
+                    self.discard();
+                    return None;
+                }
+                Err(resume) => {
+                    self.scanned = resume;
+                    // a trailing CR may yet turn out to be part of a CRLF
+                    return match self.limit {
+                        Some((limit, overflow))
+                            if content_end(buffer, resume, strip_cr) > limit =>
+                        {
+                            Some(self.overflow(limit, overflow, None))
+                        }
+                        _ => None,
+                    };
+                }
+            };
+            if self.discarding {
+                // the rest of a line that overflowed
+                self.discarding = false;
+                self.mark(consumed, true);
+                self.skip(consumed, last);
+                continue;
+            }
+            if let Some((limit, overflow)) = self.limit {
+                if end > limit {
+                    return Some(self.overflow(limit, overflow, Some((end, consumed, last))));
                 }
             }
+            let line = buffer.split_to(consumed).freeze();
+            self.skip(0, last);
+            self.partial = false;
+            return Some(Ok(Raw {
+                line: line.slice(..if self.keep_terminator { consumed } else { end }),
+                terminator: terminator(&line, end),
+                position: self.mark(consumed, true),
+            }));
         }
-        replace(&mut self.buffered, buffered);
-        None
+    }
+
+    /// Decides whether the buffer starts with a byte order mark, stripping it when
+    /// configured to, or returns false when too little has been read to tell
+    fn detect_bom(
+        &mut self,
+        flush: bool,
+    ) -> bool {
+        let buffer = match self.bytes.as_mut() {
+            Some(buffer) if flush || !BOM.starts_with(buffer) => buffer,
+            _ => return false,
+        };
+        let bom = buffer.starts_with(BOM);
+        if bom && self.strip_bom {
+            buffer.advance(BOM.len());
+            self.mark(BOM.len(), false);
+        }
+        self.bom = Some(bom);
+        true
+    }
+
+    /// Handles a line longer than `limit`. `complete` holds the ends of the line's
+    /// content and terminator, and whether it was the last line, when the whole
+    /// line is buffered.
+    fn overflow(
+        &mut self,
+        limit: usize,
+        overflow: Overflow,
+        complete: Option<(usize, usize, bool)>,
+    ) -> Result<Raw, LineTooLong> {
+        self.partial = overflow == Overflow::Split;
+        let buffer = self.bytes.as_mut().expect("overflow with an empty buffer");
+        // lines of Unicode text are only cut between characters
+        let cut = match self.delimiter {
+            Delimiter::Unicode => char_boundary(buffer, limit),
+            _ => limit,
+        };
+        match (overflow, complete) {
+            (Overflow::Split, _) => {
+                let line = buffer.split_to(cut).freeze();
+                self.scanned -= cut;
+                Ok(Raw {
+                    line,
+                    terminator: None,
+                    position: self.mark(cut, false),
+                })
+            }
+            (Overflow::Truncate, Some((end, consumed, last))) => {
+                let line = buffer.split_to(consumed).freeze();
+                self.skip(0, last);
+                let truncated = if self.keep_terminator && end < consumed {
+                    // the terminator no longer follows the content it is kept with
+                    let mut truncated = BytesMut::with_capacity(cut + consumed - end);
+                    truncated.extend_from_slice(&line[..cut]);
+                    truncated.extend_from_slice(&line[end..]);
+                    truncated.freeze()
+                } else {
+                    line.slice(..cut)
+                };
+                Ok(Raw {
+                    line: truncated,
+                    terminator: terminator(&line, end),
+                    position: self.mark(consumed, true),
+                })
+            }
+            (Overflow::Truncate, None) => {
+                let line = buffer.split_to(cut).freeze();
+                self.scanned -= cut;
+                let position = self.mark(cut, false);
+                self.discard();
+                Ok(Raw {
+                    line,
+                    terminator: None,
+                    position,
+                })
+            }
+            (Overflow::Error, Some((_, consumed, last))) => {
+                self.mark(consumed, true);
+                self.skip(consumed, last);
+                Err(LineTooLong { limit })
+            }
+            (Overflow::Error, None) => {
+                self.discard();
+                Err(LineTooLong { limit })
+            }
+        }
+    }
+
+    /// Drops what has been scanned of the line at the front of the buffer, along
+    /// with the rest of the line as it is read
+    fn discard(&mut self) {
+        let scanned = self.scanned;
+        self.discarding = true;
+        self.mark(scanned, false);
+        self.skip(scanned, false);
+    }
+
+    /// Drops the first `consumed` bytes of the buffer, or the whole buffer when
+    /// they were the `last` of it
+    fn skip(
+        &mut self,
+        consumed: usize,
+        last: bool,
+    ) {
+        self.scanned = 0;
+        match self.bytes.as_mut() {
+            Some(_) if last => self.bytes = None,
+            Some(buffer) => buffer.advance(consumed),
+            None => (),
+        }
+    }
+
+    /// Accounts for `len` bytes of the line at the front of the buffer having been
+    /// read, returning where they were read from, moving on to the next line once
+    /// it has `ended`
+    fn mark(
+        &mut self,
+        len: usize,
+        ended: bool,
+    ) -> Position {
+        let position = Position {
+            line: self.line,
+            start: self.offset,
+            end: self.offset + len as u64,
+        };
+        self.offset = position.end;
+        if ended {
+            self.line += 1;
+        }
+        position
+    }
+}
+
+/// Returns what follows the content of a `line` ending at `end`, if anything
+fn terminator(
+    line: &Bytes,
+    end: usize,
+) -> Option<Bytes> {
+    Some(line.slice(end..)).filter(|terminator| !terminator.is_empty())
+}
+
+/// Returns the last boundary between UTF-8 characters in `buffer` at or before `limit`,
+/// or `limit` itself when a single character is longer than it
+fn char_boundary(
+    buffer: &[u8],
+    limit: usize,
+) -> usize {
+    (limit.saturating_sub(3)..=limit)
+        .rev()
+        .take_while(|&at| at > 0)
+        .find(|&at| buffer.get(at).is_none_or(|b| b & 0xc0 != 0x80))
+        .unwrap_or(limit)
+}
+
+/// Returns the end of a line's content ending at `end`, less any trailing CR when `strip_cr` is set
+fn content_end(
+    buffer: &[u8],
+    end: usize,
+    strip_cr: bool,
+) -> usize {
+    match buffer[..end].last() {
+        Some(&CR) if strip_cr => end - 1,
+        _ => end,
+    }
+}
+
+impl<S, O, E, F, L> Lines<S, O, E, F, L> {
+    /// Polls for the next line, before it is converted
+    fn poll_raw<C, SE>(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Raw, SE>>>
+    where
+        S: Stream<Item = Result<C, SE>>,
+        C: AsRef<[u8]>,
+        L: Limit<SE>,
+    {
+        self.poll_split(cx)
+            .map(|line| line.map(|line| line.and_then(|line| line.map_err(L::too_long))))
+    }
+
+    /// Polls for the next line, before it is converted, telling lines that were too
+    /// long apart from errors of the underlying stream
+    fn poll_split<C, SE>(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Result<Raw, LineTooLong>, SE>>>
+    where
+        S: Stream<Item = Result<C, SE>>,
+        C: AsRef<[u8]>,
+    {
+        let mut this = self.project();
+        loop {
+            match this.buffer.next(*this.done) {
+                Some(raw) => return Poll::Ready(Some(Ok(raw))),
+                None if *this.done => return Poll::Ready(None),
+                None => (),
+            }
+            // only yield control when the underlying stream has nothing
+            // more for us, in which case it is responsible for waking us
+            match ready!(this.stream.as_mut().poll_next(cx)) {
+                None => *this.done = true,
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                Some(Ok(chunk)) => this.buffer.extend(chunk.as_ref()),
+            }
+        }
+    }
+
+    /// Converts a line polled with `poll_raw`
+    fn convert(
+        self: Pin<&mut Self>,
+        line: Bytes,
+    ) -> Result<O, E>
+    where
+        F: FnMut(Bytes) -> Result<O, E>,
+    {
+        (self.project().into)(line)
     }
 }
 
-/// This implementation should be flexible enough to work with plan strings as well as `hyper::Chunks`
+/// This implementation should be flexible enough to work with plan strings as well as `hyper` bodies.
 /// Errors from the underlying stream should be able to convert parse errors into the streams native
-/// error type using a `From` impl. this changed between hyper 0.11 and 0.12.
-impl<S, O, E> Stream for Lines<S, O, E>
+/// error type using a `From` impl.
+impl<S, C, SE, O, E, F, L> Stream for Lines<S, O, E, F, L>
 where
-    S: Stream,
-    S::Item: AsRef<[u8]>,
-    S::Error: From<E>,
+    S: Stream<Item = Result<C, SE>>,
+    C: AsRef<[u8]>,
+    SE: From<E>,
+    F: FnMut(Bytes) -> Result<O, E>,
+    L: Limit<SE>,
 {
-    type Item = O;
-    type Error = S::Error;
-    fn poll(&mut self) -> Poll<Option<O>, S::Error> {
-        match self.stream.poll()? {
-            Async::NotReady => Ok(Async::NotReady),
-            Async::Ready(None) => match self.next(true) {
-                Some(Ok(line)) => Ok(Async::Ready(Some(line))),
-                Some(Err(err)) => Err(err.into()),
-                None => Ok(Async::Ready(None)),
-            },
-            Async::Ready(Some(chunk)) => {
-                if let Some(ref mut buffer) = self.buffered {
-                    buffer.extend(chunk.as_ref());
-                } else {
-                    self.buffered = Some(chunk.as_ref().to_vec());
-                }
-                match self.next(false) {
-                    Some(Ok(line)) => Ok(Async::Ready(Some(line))),
-                    Some(Err(err)) => Err(err.into()),
-                    None => Ok(Async::NotReady),
-                }
-            }
-        }
+    type Item = Result<O, SE>;
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        Poll::Ready(match ready!(self.as_mut().poll_raw(cx)) {
+            Some(Ok(raw)) => Some(self.convert(raw.line).map_err(SE::from)),
+            Some(Err(err)) => Some(Err(err)),
+            None => None,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use futures::stream::iter_ok;
-    #[test]
-    fn it_delimits_by_lf() {
-        let chunks = vec!["hello ", "world\n", "\n", "what a\nlovely", "\nday\n"];
-        let stream = iter_ok::<_, FromUtf8Error>(chunks);
-        let mut lines = strings(stream);
-        assert_eq!(lines.poll().unwrap(), Async::NotReady);
-        assert_eq!(
-            lines.poll().unwrap(),
-            Async::Ready(Some("hello world".into()))
-        );
-        assert_eq!(lines.poll().unwrap(), Async::Ready(Some("".into())));
-        assert_eq!(lines.poll().unwrap(), Async::Ready(Some("what a".into())));
-        assert_eq!(lines.poll().unwrap(), Async::Ready(Some("lovely".into())));
-        assert_eq!(lines.poll().unwrap(), Async::Ready(Some("day".into())));
-        assert_eq!(lines.poll().unwrap(), Async::Ready(Some("".into())));
-        assert_eq!(lines.poll().unwrap(), Async::Ready(None));
+    use futures::{stream, StreamExt};
+    use futures_test::task::new_count_waker;
+
+    #[derive(Debug, PartialEq)]
+    enum TestErr {
+        Utf8(FromUtf8Error),
+        TooLong(LineTooLong),
+        Line(LineError<FromUtf8Error>),
+        Stream(&'static str),
     }
 
-    #[test]
-    fn it_delimits_by_crlf() {
-        let chunks = vec![
+    impl From<FromUtf8Error> for TestErr {
+        fn from(e: FromUtf8Error) -> Self {
+            TestErr::Utf8(e)
+        }
+    }
+
+    impl From<LineTooLong> for TestErr {
+        fn from(e: LineTooLong) -> Self {
+            TestErr::TooLong(e)
+        }
+    }
+
+    impl From<LineError<FromUtf8Error>> for TestErr {
+        fn from(e: LineError<FromUtf8Error>) -> Self {
+            TestErr::Line(e)
+        }
+    }
+
+    fn chunks(chunks: Vec<&'static str>) -> impl Stream<Item = Result<&'static str, TestErr>> {
+        stream::iter(chunks).map(Ok)
+    }
+
+    /// A chunk source that is pending exactly once, waking its task a single
+    /// time, before yielding all of its chunks back to back
+    struct WakeOnce {
+        chunks: std::vec::IntoIter<&'static str>,
+        woken: bool,
+    }
+
+    impl WakeOnce {
+        fn new(chunks: Vec<&'static str>) -> Self {
+            WakeOnce {
+                chunks: chunks.into_iter(),
+                woken: false,
+            }
+        }
+    }
+
+    impl Stream for WakeOnce {
+        type Item = Result<&'static str, TestErr>;
+        fn poll_next(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Option<Self::Item>> {
+            if !self.woken {
+                self.woken = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Poll::Ready(self.chunks.next().map(Ok))
+        }
+    }
+
+    #[tokio::test]
+    async fn it_delimits_by_lf() {
+        let mut lines = strings(chunks(vec![
+            "hello ",
+            "world\n",
+            "\n",
+            "what a\nlovely",
+            "\nday\n",
+        ]));
+        assert_eq!(lines.next().await, Some(Ok("hello world".into())));
+        assert_eq!(lines.next().await, Some(Ok("".into())));
+        assert_eq!(lines.next().await, Some(Ok("what a".into())));
+        assert_eq!(lines.next().await, Some(Ok("lovely".into())));
+        assert_eq!(lines.next().await, Some(Ok("day".into())));
+        assert_eq!(lines.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn it_delimits_by_crlf() {
+        let mut lines = strings(chunks(vec![
             "hello ",
             "world\r\n",
             "\r\n",
             "what a\r\nlovely",
             "\r\nday\r\n",
-        ];
-        let stream = iter_ok::<_, FromUtf8Error>(chunks);
-        let mut lines = strings(stream);
-        assert_eq!(lines.poll().unwrap(), Async::NotReady);
-        assert_eq!(
-            lines.poll().unwrap(),
-            Async::Ready(Some("hello world".into()))
-        );
-        assert_eq!(lines.poll().unwrap(), Async::Ready(Some("".into())));
-        assert_eq!(lines.poll().unwrap(), Async::Ready(Some("what a".into())));
-        assert_eq!(lines.poll().unwrap(), Async::Ready(Some("lovely".into())));
-        assert_eq!(lines.poll().unwrap(), Async::Ready(Some("day".into())));
-        assert_eq!(lines.poll().unwrap(), Async::Ready(Some("".into())));
-        assert_eq!(lines.poll().unwrap(), Async::Ready(None));
+        ]));
+        assert_eq!(lines.next().await, Some(Ok("hello world".into())));
+        assert_eq!(lines.next().await, Some(Ok("".into())));
+        assert_eq!(lines.next().await, Some(Ok("what a".into())));
+        assert_eq!(lines.next().await, Some(Ok("lovely".into())));
+        assert_eq!(lines.next().await, Some(Ok("day".into())));
+        assert_eq!(lines.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn it_forwards_stream_errors() {
+        let chunks = vec![Ok("hello\nworld"), Err(TestErr::Stream("boom"))];
+        let mut lines = strings(stream::iter(chunks));
+        assert_eq!(lines.next().await, Some(Ok("hello".into())));
+        assert_eq!(lines.next().await, Some(Err(TestErr::Stream("boom"))));
+        assert_eq!(lines.next().await, Some(Ok("world".into())));
+        assert_eq!(lines.next().await, None);
+    }
+
+    #[test]
+    fn it_reads_through_chunks_without_a_newline() {
+        let (waker, wakes) = new_count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut lines = strings(WakeOnce::new(vec![
+            "hello ", "world\n", "what ", "a ", "lovely", " day",
+        ]));
+        assert!(lines.poll_next_unpin(&mut cx).is_pending());
+        assert_eq!(wakes, 1);
+        assert_eq!(
+            lines.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(Ok("hello world".into())))
+        );
+        assert_eq!(
+            lines.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(Ok("what a lovely day".into())))
+        );
+        assert_eq!(lines.poll_next_unpin(&mut cx), Poll::Ready(None));
+        assert_eq!(wakes, 1);
+    }
+
+    #[tokio::test]
+    async fn it_completes_on_an_executor_with_a_single_wakeup() {
+        let lines = strings(WakeOnce::new(vec!["a", "b", "c\nd", "e"]))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(lines, vec![Ok("abc".into()), Ok("de".into())]);
+    }
+
+    #[test]
+    fn it_yields_buffered_lines_before_polling_for_more() {
+        let (waker, wakes) = new_count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut lines = strings(chunks(vec!["one\ntwo\nthree"]).chain(stream::pending()));
+        assert_eq!(
+            lines.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(Ok("one".into())))
+        );
+        assert_eq!(
+            lines.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(Ok("two".into())))
+        );
+        assert!(lines.poll_next_unpin(&mut cx).is_pending());
+        assert_eq!(wakes, 0);
+    }
+
+    #[tokio::test]
+    async fn it_yields_lines_as_bytes() {
+        let lines = bytes(chunks(vec!["hello\r\nwor", "ld\n\nbye"]))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok(Bytes::from("hello")),
+                Ok(Bytes::from("world")),
+                Ok(Bytes::new()),
+                Ok(Bytes::from("bye"))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_finds_lines_one_byte_at_a_time() {
+        let chunks = "one\r\ntwo\nthree"
+            .as_bytes()
+            .chunks(1)
+            .map(Ok::<_, TestErr>);
+        let lines = bytes(stream::iter(chunks)).collect::<Vec<_>>().await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok(Bytes::from("one")),
+                Ok(Bytes::from("two")),
+                Ok(Bytes::from("three"))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_errors_on_lines_over_max_length() {
+        let lines = strings(chunks(vec![
+            "short\nway too",
+            " long\r\nok\r",
+            "\nlong again",
+        ]))
+        .max_line_length(5, Overflow::Error)
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok("short".into()),
+                Err(TestErr::TooLong(LineTooLong { limit: 5 })),
+                Ok("ok".into()),
+                Err(TestErr::TooLong(LineTooLong { limit: 5 })),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_only_converts_line_too_long_errors_once_limited() {
+        // an error type with no From<LineTooLong> works until a limit is set
+        let lines = strings(stream::iter(vec!["abcdef\nab"]).map(Ok::<_, FromUtf8Error>))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(lines, vec![Ok("abcdef".into()), Ok("ab".into())]);
+        let lines = bytes(stream::iter(vec!["abcdef\nab"]).map(Ok::<_, io::Error>))
+            .max_line_length(4, Overflow::Error)
+            .map(|line| line.map_err(|e| e.to_string()))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Err("line exceeded the maximum length of 4 bytes".into()),
+                Ok(Bytes::from("ab"))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_truncates_lines_over_max_length() {
+        let lines = strings(chunks(vec!["abcdefgh", "ijk\nabcde\r\n", "xyz\nabcdefg"]))
+            .max_line_length(5, Overflow::Truncate)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok("abcde".into()),
+                Ok("abcde".into()),
+                Ok("xyz".into()),
+                Ok("abcde".into()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_splits_lines_over_max_length() {
+        let mut lines = strings(chunks(vec!["abcdefg", "hijk\nxy\nabcdef"]))
+            .max_line_length(4, Overflow::Split);
+        let mut pieces = vec![];
+        while let Some(line) = lines.next().await {
+            pieces.push((line.unwrap(), lines.is_partial()));
+        }
+        assert_eq!(
+            pieces,
+            vec![
+                ("abcd".into(), true),
+                ("efgh".into(), true),
+                ("ijk".into(), false),
+                ("xy".into(), false),
+                ("abcd".into(), true),
+                ("ef".into(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_does_not_buffer_overflowing_lines() {
+        let (waker, _) = new_count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut lines = bytes(chunks(vec!["abcdefgh", "ijklmnop"]).chain(stream::pending()))
+            .max_line_length(4, Overflow::Truncate);
+        assert_eq!(
+            lines.poll_next_unpin(&mut cx),
+            Poll::Ready(Some(Ok(Bytes::from("abcd"))))
+        );
+        assert!(lines.poll_next_unpin(&mut cx).is_pending());
+        assert_eq!(lines.buffer.bytes.as_ref().map(BytesMut::len), Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "max_line_length must be at least 1 byte")]
+    fn it_rejects_a_max_line_length_of_zero() {
+        let _ = strings(chunks(vec!["ab\n\n"])).max_line_length(0, Overflow::Split);
+    }
+
+    #[test]
+    #[should_panic(expected = "max_line_length must be at least 1 byte")]
+    fn it_rejects_a_built_max_line_length_of_zero() {
+        let _ = builder().max_line_length(0, Overflow::Split);
+    }
+
+    #[tokio::test]
+    async fn it_builds_lines_with_a_delimiter() {
+        let lines = builder()
+            .delimiter(b';')
+            .capacity(64)
+            .strings(chunks(vec!["a\r;b", "\n;", "c"]))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![Ok("a\r".into()), Ok("b\n".into()), Ok("c".into())]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_builds_lines_keeping_terminators() {
+        let lines = builder()
+            .keep_terminator(true)
+            .strings(chunks(vec!["a\r\nb\n", "c"]))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![Ok("a\r\n".into()), Ok("b\n".into()), Ok("c".into())]
+        );
+        let lines = builder()
+            .keep_terminator(true)
+            .max_line_length(3, Overflow::Truncate)
+            .strings(chunks(vec!["abcdef\nxy\nabcd\r\nabcd"]))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok("abc\n".into()),
+                Ok("xy\n".into()),
+                Ok("abc\r\n".into()),
+                Ok("abc".into())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_builds_lines_without_stripping_cr() {
+        let lines = builder()
+            .strip_cr(false)
+            .max_line_length(2, Overflow::Error)
+            .strings(chunks(vec!["a\r\nbc\r\n"]))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok("a\r".into()),
+                Err(TestErr::TooLong(LineTooLong { limit: 2 })),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_converts_lines_with_closures() {
+        let mut seen = 0;
+        let lines = map_lines(chunks(vec!["a\nb", "\nc"]), move |line| {
+            seen += 1;
+            String::from_utf8(line.into()).map(|line| format!("{}: {}", seen, line))
+        })
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(
+            lines,
+            vec![Ok("1: a".into()), Ok("2: b".into()), Ok("3: c".into())]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_delimits_by_nul() {
+        let lines = null_terminated(chunks(vec!["./a b\n\0./c", "\r\0"]))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(lines, vec![Ok("./a b\n".into()), Ok("./c\r".into())]);
+    }
+
+    #[tokio::test]
+    async fn it_delimits_by_record_separator() {
+        let lines =
+            Lines::with_delimiter(chunks(vec!["one\x1etw", "o\x1e"]), 0x1e, Ok::<_, TestErr>)
+                .collect::<Vec<_>>()
+                .await;
+        assert_eq!(lines, vec![Ok(Bytes::from("one")), Ok(Bytes::from("two"))]);
+    }
+
+    #[tokio::test]
+    async fn it_delimits_by_sequences_split_at_every_offset() {
+        for delimiter in &["\r\n\r\n", "\n\n", "--boundary--"] {
+            let input = format!("one{d}two\nlines{d}{d}three", d = delimiter);
+            for split in 0..=input.len() {
+                for second in split..=input.len() {
+                    let pieces = vec![
+                        input[..split].to_string(),
+                        input[split..second].to_string(),
+                        input[second..].to_string(),
+                    ];
+                    let lines = builder()
+                        .delimiter(*delimiter)
+                        .strings(stream::iter(pieces).map(Ok::<_, TestErr>))
+                        .collect::<Vec<_>>()
+                        .await;
+                    assert_eq!(
+                        lines,
+                        vec![
+                            Ok("one".into()),
+                            Ok("two\nlines".into()),
+                            Ok("".into()),
+                            Ok("three".into())
+                        ],
+                        "{:?} split at {} and {}",
+                        delimiter,
+                        split,
+                        second
+                    );
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn it_keeps_sequences_split_by_overflowing_lines() {
+        let lines = builder()
+            .delimiter("\r\n\r\n")
+            .max_line_length(4, Overflow::Truncate)
+            .strings(chunks(vec!["abcdefgh\r\n", "\r\nij\r", "\n\r\n"]))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(lines, vec![Ok("abcd".into()), Ok("ij".into())]);
+    }
+
+    #[tokio::test]
+    async fn it_delimits_by_universal_newlines_split_at_every_offset() {
+        let input = "10%\r20%\r\rdone\r\n\nbye\r";
+        for split in 0..=input.len() {
+            let lines = builder()
+                .delimiter(Delimiter::Universal)
+                .strings(chunks(vec![&input[..split], &input[split..]]))
+                .collect::<Vec<_>>()
+                .await;
+            assert_eq!(
+                lines,
+                vec![
+                    Ok("10%".into()),
+                    Ok("20%".into()),
+                    Ok("".into()),
+                    Ok("done".into()),
+                    Ok("".into()),
+                    Ok("bye".into())
+                ],
+                "split at {}",
+                split
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_delimits_by_unicode_separators_split_at_every_offset() {
+        let input = "caf\u{e9}\u{85}line\u{2028}para\u{2029}\r\nform\x0cfeed\u{2030}";
+        for split in 0..=input.len() {
+            let pieces = vec![&input.as_bytes()[..split], &input.as_bytes()[split..]];
+            let lines = unicode_strings(stream::iter(pieces).map(Ok::<_, TestErr>))
+                .collect::<Vec<_>>()
+                .await;
+            assert_eq!(
+                lines,
+                vec![
+                    Ok("caf\u{e9}".into()),
+                    Ok("line".into()),
+                    Ok("para".into()),
+                    Ok("".into()),
+                    Ok("form".into()),
+                    Ok("feed\u{2030}".into())
+                ],
+                "split at {}",
+                split
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_cuts_unicode_lines_over_max_length_between_characters() {
+        let input = "h\u{e9}llo\n\u{e9}\u{e9}\u{e9}\n";
+        for split in 0..=input.len() {
+            let pieces = vec![&input.as_bytes()[..split], &input.as_bytes()[split..]];
+            let lines = unicode_strings(stream::iter(pieces).map(Ok::<_, TestErr>))
+                .max_line_length(2, Overflow::Split)
+                .collect::<Vec<_>>()
+                .await;
+            assert_eq!(
+                lines,
+                vec![
+                    Ok("h".into()),
+                    Ok("\u{e9}".into()),
+                    Ok("ll".into()),
+                    Ok("o".into()),
+                    Ok("\u{e9}".into()),
+                    Ok("\u{e9}".into()),
+                    Ok("\u{e9}".into()),
+                ],
+                "split at {}",
+                split
+            );
+        }
+        let lines = unicode_strings(chunks(vec!["h\u{e9}llo\n"]))
+            .max_line_length(2, Overflow::Truncate)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(lines, vec![Ok("h".into())]);
+    }
+
+    #[tokio::test]
+    async fn it_reports_terminators() {
+        let lines = builder()
+            .delimiter(Delimiter::Universal)
+            .strings(chunks(vec!["a\r\nb\nc\r", "d"]))
+            .with_terminators()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok(Line {
+                    content: "a".into(),
+                    terminator: Some(Terminator::CrLf)
+                }),
+                Ok(Line {
+                    content: "b".into(),
+                    terminator: Some(Terminator::Lf)
+                }),
+                Ok(Line {
+                    content: "c".into(),
+                    terminator: Some(Terminator::Cr)
+                }),
+                Ok(Line {
+                    content: "d".into(),
+                    terminator: None
+                }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_round_trips_lines_with_terminators() {
+        let input = "one\r\ntwo\n\nthree--four\r--five\r";
+        for delimiter in [
+            Delimiter::Byte(LF),
+            Delimiter::from("--"),
+            Delimiter::Universal,
+        ] {
+            for overflow in [None, Some(Overflow::Split), Some(Overflow::Truncate)] {
+                let lines = builder()
+                    .delimiter(delimiter.clone())
+                    .bytes(chunks(vec![&input[..7], &input[7..]]))
+                    .with_limit::<Limited>(overflow.map(|overflow| (3, overflow)))
+                    .with_terminators()
+                    .collect::<Vec<_>>()
+                    .await;
+                let mut output = vec![];
+                for line in lines {
+                    let line = line.unwrap();
+                    output.extend_from_slice(&line.content);
+                    output.extend(line.terminator.iter().flat_map(Terminator::as_bytes));
+                }
+                if overflow == Some(Overflow::Truncate) {
+                    assert!(output.len() < input.len(), "{:?}", delimiter);
+                } else {
+                    assert_eq!(
+                        String::from_utf8(output).unwrap(),
+                        input,
+                        "{:?} {:?}",
+                        delimiter,
+                        overflow
+                    );
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn it_yields_nothing_for_empty_input() {
+        for input in [vec![], vec![""], vec!["", ""]] {
+            let lines = strings(chunks(input)).collect::<Vec<_>>().await;
+            assert_eq!(lines, vec![]);
+        }
+    }
+
+    #[tokio::test]
+    async fn it_yields_empty_lines_for_only_newlines() {
+        let lines = strings(chunks(vec!["\n", "\r\n\n"]))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(lines, vec![Ok("".into()), Ok("".into()), Ok("".into())]);
+    }
+
+    #[tokio::test]
+    async fn it_yields_lines_without_a_trailing_newline() {
+        let lines = strings(chunks(vec!["a\nb"])).collect::<Vec<_>>().await;
+        assert_eq!(lines, vec![Ok("a".into()), Ok("b".into())]);
+    }
+
+    #[tokio::test]
+    async fn it_optionally_yields_a_trailing_empty_line() {
+        let lines = builder()
+            .trailing_empty_line(true)
+            .strings(chunks(vec!["a\n", "b\n"]))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(lines, vec![Ok("a".into()), Ok("b".into()), Ok("".into())]);
+        let lines = builder()
+            .trailing_empty_line(true)
+            .strings(chunks(vec![]))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(lines, vec![]);
+    }
+
+    fn position(
+        line: u64,
+        start: u64,
+        end: u64,
+    ) -> Position {
+        Position { line, start, end }
+    }
+
+    #[tokio::test]
+    async fn it_reports_positions_across_chunks() {
+        let lines = strings(chunks(vec!["one\r", "\ntw", "o\n\nthr", "ee"]))
+            .with_positions()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok((position(1, 0, 5), "one".into())),
+                Ok((position(2, 5, 9), "two".into())),
+                Ok((position(3, 9, 10), "".into())),
+                Ok((position(4, 10, 15), "three".into())),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_reports_positions_of_overflowing_lines() {
+        let input = || chunks(vec!["abcdefg", "hij\nxy\n", "abcdef"]);
+        let lines = strings(input())
+            .max_line_length(4, Overflow::Split)
+            .with_positions()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok((position(1, 0, 4), "abcd".into())),
+                Ok((position(1, 4, 8), "efgh".into())),
+                Ok((position(1, 8, 11), "ij".into())),
+                Ok((position(2, 11, 14), "xy".into())),
+                Ok((position(3, 14, 18), "abcd".into())),
+                Ok((position(3, 18, 20), "ef".into())),
+            ]
+        );
+        let lines = strings(input())
+            .max_line_length(4, Overflow::Truncate)
+            .with_positions()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok((position(1, 0, 4), "abcd".into())),
+                Ok((position(2, 11, 14), "xy".into())),
+                Ok((position(3, 14, 18), "abcd".into())),
+            ]
+        );
+        let lines = strings(input())
+            .max_line_length(4, Overflow::Error)
+            .with_positions()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Err(TestErr::TooLong(LineTooLong { limit: 4 })),
+                Ok((position(2, 11, 14), "xy".into())),
+                Err(TestErr::TooLong(LineTooLong { limit: 4 })),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_attaches_context_to_conversion_errors() {
+        let mut long = vec![b'x'; 70];
+        long.push(0xff);
+        let bad = [&b"ok\r\n"[..], b"caf", b"\xff\n", &long, b"\nfine"];
+        let lines = strings(stream::iter(bad).map(Ok::<_, TestErr>))
+            .with_error_context()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], Ok("ok".into()));
+        assert_eq!(lines[3], Ok("fine".into()));
+        match &lines[1] {
+            Err(TestErr::Line(err)) => {
+                assert_eq!((err.line(), err.offset()), (2, 4));
+                assert_eq!(err.preview(), b"caf\xff");
+                assert_eq!(err.get_ref().utf8_error().valid_up_to(), 3);
+                assert_eq!(
+                    err.to_string(),
+                    "line 2, byte 4: invalid utf-8 sequence of 1 bytes from index 3 in \"caf\\xff\""
+                );
+            }
+            other => panic!("expected a line error, got {:?}", other),
+        }
+        match &lines[2] {
+            Err(TestErr::Line(err)) => {
+                assert_eq!(err.position(), position(3, 9, 81));
+                assert_eq!(err.preview(), "x".repeat(64).as_bytes());
+                assert!(err.to_string().ends_with("x...\""));
+            }
+            other => panic!("expected a line error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn it_recovers_from_conversion_errors() {
+        let input = || {
+            stream::iter(vec![
+                Ok(&b"a\n\xff\n"[..]),
+                Err(TestErr::Stream("boom")),
+                Ok(b"toolong\nb"),
+            ])
+        };
+        let lines = strings(input())
+            .max_line_length(4, Overflow::Error)
+            .recover()
+            .map(|line| line.map(|line| line.map_err(|e| e.utf8_error().valid_up_to())))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok(Ok("a".into())),
+                Ok(Err(0)),
+                Err(TestErr::Stream("boom")),
+                Err(TestErr::TooLong(LineTooLong { limit: 4 })),
+                Ok(Ok("b".into())),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_skips_conversion_errors() {
+        let mut skipped = vec![];
+        let lines = strings(stream::iter(vec![
+            Ok(&b"a\n\xff"[..]),
+            Ok(b"\xfe\nb\n"),
+            Err(TestErr::Stream("boom")),
+        ]))
+        .skip_errors(|err| skipped.push(err.position()))
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(
+            lines,
+            vec![Ok("a".into()), Ok("b".into()), Err(TestErr::Stream("boom"))]
+        );
+        assert_eq!(skipped, vec![position(2, 2, 5)]);
+    }
+
+    #[tokio::test]
+    async fn it_replaces_invalid_utf8_in_lossy_strings() {
+        let bad = [&b"caf\xc3"[..], b"\xa9\nb\xffd\xff\r\n", b"\xe2\x80"];
+        let lines = strings_lossy(stream::iter(bad).map(Ok::<_, TestErr>))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok("caf\u{e9}".into()),
+                Ok("b\u{fffd}d\u{fffd}".into()),
+                Ok("\u{fffd}".into())
+            ]
+        );
+        let lines = builder()
+            .strings_lossy_counted(stream::iter(bad).map(Ok::<_, TestErr>))
+            .map(|line| line.map(|line| line.replacements))
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(lines, vec![Ok(0), Ok(2), Ok(1)]);
+    }
+
+    #[tokio::test]
+    async fn it_strips_byte_order_marks_split_at_every_offset() {
+        let input = "\u{feff}a\r\nb\n";
+        for at in 0..=input.len() {
+            let (first, second) = input.as_bytes().split_at(at);
+            let mut lines = builder()
+                .strip_bom(true)
+                .bytes(stream::iter([first, second]).map(Ok::<_, TestErr>));
+            assert_eq!(lines.next().await, Some(Ok(Bytes::from("a"))));
+            assert!(lines.has_bom());
+            assert_eq!(lines.next().await, Some(Ok(Bytes::from("b"))));
+            assert_eq!(lines.next().await, None);
+        }
+        let lines = builder()
+            .strip_bom(true)
+            .strings(chunks(vec![input]))
+            .with_positions()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            lines,
+            vec![
+                Ok((position(1, 3, 6), "a".into())),
+                Ok((position(2, 6, 8), "b".into()))
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_detects_byte_order_marks() {
+        let mut lines = strings(chunks(vec!["\u{feff}a\n"]));
+        assert_eq!(lines.next().await, Some(Ok("\u{feff}a".into())));
+        assert!(lines.has_bom());
+        for input in ["\u{feff}", "\u{feff}\n", "\u{fefe}\n", "\n", "ab", "\u{fe}"] {
+            let mut lines = builder().strip_bom(true).strings(chunks(vec![input]));
+            let expected = input.strip_prefix('\u{feff}').unwrap_or(input);
+            let first = lines.next().await;
+            assert_eq!(
+                lines.has_bom(),
+                input.starts_with('\u{feff}'),
+                "{:?}",
+                input
+            );
+            assert_eq!(
+                first,
+                expected.lines().next().map(|line| Ok(line.into())),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn it_lends_lines_from_the_buffer() {
+        let (waker, _) = new_count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut lines = strings(chunks(vec!["one\r\ntw", "o\n", "six\n", "ten"]));
+        let mut lent = vec![];
+        while let Poll::Ready(Some(line)) = Pin::new(&mut lines).poll_line_ref(&mut cx) {
+            let line = line.unwrap();
+            lent.push((line.to_vec(), line.as_ptr()));
+        }
+        assert_eq!(
+            lent.iter().map(|(line, _)| &line[..]).collect::<Vec<_>>(),
+            vec![&b"one"[..], b"two", b"six", b"ten"]
+        );
+        // once a line is released, the space it took up is read into again
+        // rather than allocating for the lines after it
+        let start = lent[0].1 as usize;
+        for (_, ptr) in &lent {
+            assert!((*ptr as usize).wrapping_sub(start) < 8);
+        }
+    }
+
+    #[tokio::test]
+    async fn it_calls_back_with_each_line() {
+        let mut matched = vec![];
+        let result = strings(chunks(vec!["foo\nbar\nf", "oo\n"]).chain(stream::iter(vec![
+            Err(TestErr::Stream("boom")),
+            Ok("foo\n"),
+        ])))
+        .for_each_line(|line| {
+            if line == b"foo" {
+                matched.push(line.len());
+            }
+        })
+        .await;
+        assert_eq!(result, Err(TestErr::Stream("boom")));
+        assert_eq!(matched, vec![3, 3]);
     }
 }
@@ -0,0 +1,120 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_core::{ready, Stream};
+use pin_project_lite::pin_project;
+
+use crate::{Limit, Lines, Unlimited};
+
+/// A converted line along with the terminator that ended it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Line<O> {
+    /// The line, converted from its bytes
+    pub content: O,
+    /// The terminator that ended the line, if any. Only the last line of a stream,
+    /// and pieces of lines cut short by `max_line_length`, have none.
+    pub terminator: Option<Terminator>,
+}
+
+/// The bytes that ended a line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Terminator {
+    /// LF (\n)
+    Lf,
+    /// CRLF (\r\n)
+    CrLf,
+    /// A lone CR (\r)
+    Cr,
+    /// Any other delimiter
+    Custom(Bytes),
+}
+
+impl Terminator {
+    /// Returns the bytes of this terminator as they were read
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Terminator::Lf => b"\n",
+            Terminator::CrLf => b"\r\n",
+            Terminator::Cr => b"\r",
+            Terminator::Custom(bytes) => bytes,
+        }
+    }
+}
+
+impl From<Bytes> for Terminator {
+    fn from(bytes: Bytes) -> Self {
+        match &bytes[..] {
+            b"\n" => Terminator::Lf,
+            b"\r\n" => Terminator::CrLf,
+            b"\r" => Terminator::Cr,
+            _ => Terminator::Custom(bytes),
+        }
+    }
+}
+
+pin_project! {
+    /// Stream returned by `Lines::with_terminators`
+    pub struct Terminated<S, O, E, F, L = Unlimited> {
+        #[pin]
+        lines: Lines<S, O, E, F, L>,
+    }
+}
+
+impl<S, O, E, F, L> Lines<S, O, E, F, L> {
+    /// Yields each line as a `Line`, reporting the terminator it ended with, so that
+    /// the bytes read can be reproduced exactly
+    ///
+    /// ```
+    /// use std::io;
+    ///
+    /// use futures::{stream, StreamExt};
+    /// use stream_lines::{Bytes, Line, Terminator};
+    ///
+    /// # futures::executor::block_on(async {
+    /// let chunks = stream::iter(vec!["one\r\ntwo"]).map(Ok::<_, io::Error>);
+    /// let mut lines = stream_lines::bytes(chunks).with_terminators();
+    /// assert_eq!(
+    ///     lines.next().await.unwrap().unwrap(),
+    ///     Line { content: Bytes::from("one"), terminator: Some(Terminator::CrLf) }
+    /// );
+    /// assert_eq!(
+    ///     lines.next().await.unwrap().unwrap(),
+    ///     Line { content: Bytes::from("two"), terminator: None }
+    /// );
+    /// # })
+    /// ```
+    pub fn with_terminators(self) -> Terminated<S, O, E, F, L> {
+        Terminated { lines: self }
+    }
+}
+
+impl<S, C, SE, O, E, F, L> Stream for Terminated<S, O, E, F, L>
+where
+    S: Stream<Item = Result<C, SE>>,
+    C: AsRef<[u8]>,
+    SE: From<E>,
+    F: FnMut(Bytes) -> Result<O, E>,
+    L: Limit<SE>,
+{
+    type Item = Result<Line<O>, SE>;
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut lines = self.project().lines;
+        Poll::Ready(match ready!(lines.as_mut().poll_raw(cx)) {
+            Some(Ok(raw)) => Some(
+                lines
+                    .convert(raw.line)
+                    .map(|content| Line {
+                        content,
+                        terminator: raw.terminator.map(Terminator::from),
+                    })
+                    .map_err(SE::from),
+            ),
+            Some(Err(err)) => Some(Err(err)),
+            None => None,
+        })
+    }
+}
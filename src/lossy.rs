@@ -0,0 +1,53 @@
+use bytes::Bytes;
+
+/// A line decoded from UTF-8 with any invalid sequences replaced by U+FFFD
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Lossy {
+    /// The decoded line
+    pub text: String,
+    /// The number of invalid sequences that were replaced, which is non-zero
+    /// only when the line was not valid UTF-8
+    pub replacements: usize,
+}
+
+pub(crate) fn lossy<E>(line: Bytes) -> Result<String, E> {
+    lossy_counted(line).map(|lossy| lossy.text)
+}
+
+pub(crate) fn lossy_counted<E>(line: Bytes) -> Result<Lossy, E> {
+    let bytes = match String::from_utf8(line.into()) {
+        // reuses the line's allocation when it is valid
+        Ok(text) => {
+            return Ok(Lossy {
+                text,
+                replacements: 0,
+            })
+        }
+        Err(err) => err.into_bytes(),
+    };
+    let mut lossy = Lossy {
+        text: String::with_capacity(bytes.len()),
+        replacements: 0,
+    };
+    for chunk in bytes.utf8_chunks() {
+        lossy.text.push_str(chunk.valid());
+        if !chunk.invalid().is_empty() {
+            lossy.text.push(char::REPLACEMENT_CHARACTER);
+            lossy.replacements += 1;
+        }
+    }
+    Ok(lossy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_counts_replacements() {
+        let lossy = lossy_counted::<()>(Bytes::from_static(b"a\xffb\xe2\x80c\xf0")).unwrap();
+        assert_eq!(lossy.text, "a\u{fffd}b\u{fffd}c\u{fffd}");
+        assert_eq!(lossy.replacements, 3);
+        assert_eq!(lossy.text, String::from_utf8_lossy(b"a\xffb\xe2\x80c\xf0"));
+    }
+}
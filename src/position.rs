@@ -0,0 +1,84 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_core::{ready, Stream};
+use pin_project_lite::pin_project;
+
+use crate::{Limit, Lines, Unlimited};
+
+/// Where a line was read from in a stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Position {
+    /// The 1-based number of the line. Pieces of a line split by `max_line_length`
+    /// share its number.
+    pub line: u64,
+    /// The offset of the line's first byte in the stream
+    pub start: u64,
+    /// The offset just past the line's last byte, including its terminator whether
+    /// or not it was stripped
+    pub end: u64,
+}
+
+pin_project! {
+    /// Stream returned by `Lines::with_positions`
+    pub struct Positioned<S, O, E, F, L = Unlimited> {
+        #[pin]
+        lines: Lines<S, O, E, F, L>,
+    }
+}
+
+impl<S, O, E, F, L> Lines<S, O, E, F, L> {
+    /// Yields each line along with its `Position`, counting lines and bytes from the
+    /// start of the stream
+    ///
+    /// ```
+    /// use std::io;
+    ///
+    /// use futures::{stream, StreamExt};
+    /// use stream_lines::{Bytes, Position};
+    ///
+    /// # futures::executor::block_on(async {
+    /// let chunks = stream::iter(vec!["one\r\ntw", "o"]).map(Ok::<_, io::Error>);
+    /// let mut lines = stream_lines::bytes(chunks).with_positions();
+    /// assert_eq!(
+    ///     lines.next().await.unwrap().unwrap(),
+    ///     (Position { line: 1, start: 0, end: 5 }, Bytes::from("one"))
+    /// );
+    /// assert_eq!(
+    ///     lines.next().await.unwrap().unwrap(),
+    ///     (Position { line: 2, start: 5, end: 8 }, Bytes::from("two"))
+    /// );
+    /// # })
+    /// ```
+    pub fn with_positions(self) -> Positioned<S, O, E, F, L> {
+        Positioned { lines: self }
+    }
+}
+
+impl<S, C, SE, O, E, F, L> Stream for Positioned<S, O, E, F, L>
+where
+    S: Stream<Item = Result<C, SE>>,
+    C: AsRef<[u8]>,
+    SE: From<E>,
+    F: FnMut(Bytes) -> Result<O, E>,
+    L: Limit<SE>,
+{
+    type Item = Result<(Position, O), SE>;
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut lines = self.project().lines;
+        Poll::Ready(match ready!(lines.as_mut().poll_raw(cx)) {
+            Some(Ok(raw)) => Some(
+                lines
+                    .convert(raw.line)
+                    .map(|line| (raw.position, line))
+                    .map_err(SE::from),
+            ),
+            Some(Err(err)) => Some(Err(err)),
+            None => None,
+        })
+    }
+}
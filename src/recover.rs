@@ -0,0 +1,140 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use futures_core::{ready, Stream};
+use pin_project_lite::pin_project;
+
+use crate::{Limit, LineError, Lines, Unlimited};
+
+pin_project! {
+    /// Stream returned by `Lines::recover`
+    pub struct Recovering<S, O, E, F, L = Unlimited> {
+        #[pin]
+        lines: Lines<S, O, E, F, L>,
+    }
+}
+
+pin_project! {
+    /// Stream returned by `Lines::skip_errors`
+    pub struct SkipErrors<S, O, E, F, G, L = Unlimited> {
+        #[pin]
+        lines: Lines<S, O, E, F, L>,
+        on_error: G,
+    }
+}
+
+impl<S, O, E, F, L> Lines<S, O, E, F, L> {
+    /// Yields the result of converting each line as an item of its own, so that a line
+    /// that fails to convert does not end a `try_` combinator, leaving only errors
+    /// from the underlying stream, and any `LineTooLong`, in the stream's error position
+    ///
+    /// ```
+    /// use std::io;
+    ///
+    /// use futures::{stream, StreamExt, TryStreamExt};
+    ///
+    /// # futures::executor::block_on(async {
+    /// let chunks = stream::iter(vec!["1\nx\n3"]).map(Ok::<_, io::Error>);
+    /// let numbers = stream_lines::map_lines(chunks, |line| {
+    ///     String::from_utf8_lossy(&line).parse::<u32>()
+    /// })
+    /// .recover()
+    /// .try_filter_map(|number| async move { Ok(number.ok()) })
+    /// .try_collect::<Vec<_>>()
+    /// .await
+    /// .unwrap();
+    /// assert_eq!(numbers, vec![1, 3]);
+    /// # })
+    /// ```
+    pub fn recover(self) -> Recovering<S, O, E, F, L> {
+        Recovering { lines: self }
+    }
+
+    /// Skips lines that fail to convert, handing their errors to `on_error` along with
+    /// where the line was read from
+    ///
+    /// ```
+    /// use std::io;
+    ///
+    /// use futures::{stream, StreamExt, TryStreamExt};
+    ///
+    /// # futures::executor::block_on(async {
+    /// let chunks = stream::iter(vec!["1\nx\n3"]).map(Ok::<_, io::Error>);
+    /// let mut skipped = vec![];
+    /// let numbers = stream_lines::map_lines(chunks, |line| {
+    ///     String::from_utf8_lossy(&line).parse::<u32>()
+    /// })
+    /// .skip_errors(|err| skipped.push(err.line()))
+    /// .try_collect::<Vec<_>>()
+    /// .await
+    /// .unwrap();
+    /// assert_eq!(numbers, vec![1, 3]);
+    /// assert_eq!(skipped, vec![2]);
+    /// # })
+    /// ```
+    pub fn skip_errors<G>(
+        self,
+        on_error: G,
+    ) -> SkipErrors<S, O, E, F, G, L>
+    where
+        G: FnMut(LineError<E>),
+    {
+        SkipErrors {
+            lines: self,
+            on_error,
+        }
+    }
+}
+
+impl<S, C, SE, O, E, F, L> Stream for Recovering<S, O, E, F, L>
+where
+    S: Stream<Item = Result<C, SE>>,
+    C: AsRef<[u8]>,
+    F: FnMut(Bytes) -> Result<O, E>,
+    L: Limit<SE>,
+{
+    type Item = Result<Result<O, E>, SE>;
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut lines = self.project().lines;
+        Poll::Ready(match ready!(lines.as_mut().poll_raw(cx)) {
+            Some(Ok(raw)) => Some(Ok(lines.convert(raw.line))),
+            Some(Err(err)) => Some(Err(err)),
+            None => None,
+        })
+    }
+}
+
+impl<S, C, SE, O, E, F, G, L> Stream for SkipErrors<S, O, E, F, G, L>
+where
+    S: Stream<Item = Result<C, SE>>,
+    C: AsRef<[u8]>,
+    F: FnMut(Bytes) -> Result<O, E>,
+    G: FnMut(LineError<E>),
+    L: Limit<SE>,
+{
+    type Item = Result<O, SE>;
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let mut lines = this.lines;
+        loop {
+            return Poll::Ready(match ready!(lines.as_mut().poll_raw(cx)) {
+                Some(Ok(raw)) => match lines.as_mut().convert_in_context(raw) {
+                    Ok(line) => Some(Ok(line)),
+                    Err(err) => {
+                        (this.on_error)(err);
+                        continue;
+                    }
+                },
+                Some(Err(err)) => Some(Err(err)),
+                None => None,
+            });
+        }
+    }
+}
@@ -0,0 +1,440 @@
+//! [Server-Sent Events](https://html.spec.whatwg.org/multipage/server-sent-events.html),
+//! as served with a `text/event-stream` content type
+//!
+//! `events` parses a stream of bytes into `Event`s, `reconnect` keeps doing so across
+//! dropped connections, and `encode` writes `Event`s back out for serving them.
+//!
+//! ```
+//! use std::io;
+//!
+//! use futures::{stream, StreamExt};
+//! use stream_lines::sse::{self, Event};
+//!
+//! # futures::executor::block_on(async {
+//! let body = stream::iter(vec![": hello\n\nevent: add\ndata: 7385", "\ndata: 7293\nid: 1\n\n"]);
+//! let mut events = sse::events(body.map(Ok::<_, io::Error>));
+//! assert_eq!(
+//!     events.next().await.unwrap().unwrap(),
+//!     Event {
+//!         id: Some("1".into()),
+//!         event: "add".into(),
+//!         data: "7385\n7293".into(),
+//!         retry: None,
+//!     }
+//! );
+//! assert_eq!(events.last_event_id(), "1");
+//! # })
+//! ```
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures_core::{ready, Stream};
+use memchr::memchr;
+use pin_project_lite::pin_project;
+
+use crate::{builder, Convert, Delimiter, Limit, Limited, LineTooLong, Lines, Overflow, Unlimited};
+
+mod encode;
+mod reconnect;
+
+pub use encode::{encode, Encode};
+pub use reconnect::{reconnect, Reconnect};
+
+/// An event dispatched by a blank line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// The last event id the stream set, which carries over to the events after it
+    /// until another `id` field changes it. `None` until one is set, or after one is
+    /// cleared by an empty `id` field
+    pub id: Option<String>,
+    /// The type of event, which is "message" unless an `event` field says otherwise
+    pub event: String,
+    /// The values of the event's `data` fields, joined by LF (\n)
+    pub data: String,
+    /// The reconnection time set by a `retry` field in this event, if any
+    pub retry: Option<Duration>,
+}
+
+/// A stream of `Event`s parsed from a stream of bytes
+pub fn events<S, C, E>(s: S) -> Events<S, E>
+where
+    S: Stream<Item = Result<C, E>>,
+{
+    Events {
+        lines: builder()
+            .delimiter(Delimiter::Universal)
+            .strip_bom(true)
+            .bytes(s),
+        parser: Parser::default(),
+    }
+}
+
+pin_project! {
+    /// Stream returned by `events`
+    pub struct Events<S, E, L = Unlimited> {
+        #[pin]
+        lines: Lines<S, Bytes, E, Convert<Bytes, E>, L>,
+        parser: Parser,
+    }
+}
+
+/// The state of the event being parsed
+#[derive(Default)]
+struct Parser {
+    last_event_id: String,
+    /// the id set by the event being parsed, which only becomes the last event id
+    /// once the event is dispatched
+    id_buffer: String,
+    event: String,
+    data: String,
+    retry: Option<Duration>,
+    /// the reconnection time set by the event being parsed
+    event_retry: Option<Duration>,
+    /// true while skipping the rest of an event with a line that was too long
+    discarding: bool,
+}
+
+impl<S, E, L> Events<S, E, L> {
+    /// Limits the lines events are made up of to `limit` bytes. An event with a line
+    /// that is longer is never dispatched, and a `LineTooLong` error is yielded in its
+    /// place. The error can be recovered from: polling again skips the rest of that
+    /// event and yields the events that follow it.
+    /// See [Lines::max_line_length](../struct.Lines.html#method.max_line_length)
+    ///
+    /// # Panics
+    ///
+    /// When `limit` is 0
+    pub fn max_line_length(
+        self,
+        limit: usize,
+    ) -> Events<S, E, Limited> {
+        assert!(limit > 0, "max_line_length must be at least 1 byte");
+        self.with_limit(Some(limit))
+    }
+
+    /// Replaces the limit lines are held to. See `Lines::with_limit`
+    fn with_limit<M>(
+        self,
+        limit: Option<usize>,
+    ) -> Events<S, E, M> {
+        Events {
+            lines: self
+                .lines
+                .with_limit(limit.map(|limit| (limit, Overflow::Error))),
+            parser: self.parser,
+        }
+    }
+
+    /// The last event id the stream set, to be sent as the `Last-Event-ID` header
+    /// when reconnecting. Empty until one is set by an event that was dispatched
+    pub fn last_event_id(&self) -> &str {
+        &self.parser.last_event_id
+    }
+
+    /// The reconnection time the stream last set with a `retry` field, if any
+    pub fn retry(&self) -> Option<Duration> {
+        self.parser.retry
+    }
+
+    /// Carries the last event id over from an earlier connection
+    fn resume(
+        &mut self,
+        last_event_id: &str,
+    ) {
+        self.parser.last_event_id = last_event_id.to_owned();
+        self.parser.id_buffer = last_event_id.to_owned();
+    }
+}
+
+impl Parser {
+    /// Interprets a line of an event, returning the event when the line dispatches it
+    fn field(
+        &mut self,
+        line: &[u8],
+    ) -> Option<Event> {
+        if self.discarding {
+            self.discarding = !line.is_empty();
+            return None;
+        }
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        let (name, value) = match memchr(b':', line) {
+            // a comment
+            Some(0) => return None,
+            Some(at) => (&line[..at], &line[at + 1..]),
+            None => (line, &[][..]),
+        };
+        let value = value.strip_prefix(b" ").unwrap_or(value);
+        match name {
+            b"event" => self.event = String::from_utf8_lossy(value).into_owned(),
+            b"data" => {
+                self.data.push_str(&String::from_utf8_lossy(value));
+                self.data.push('\n');
+            }
+            b"id" if memchr(0, value).is_none() => {
+                self.id_buffer = String::from_utf8_lossy(value).into_owned()
+            }
+            b"retry" if !value.is_empty() && value.iter().all(u8::is_ascii_digit) => {
+                // values too large for a u64 are as good as never retrying
+                let millis = std::str::from_utf8(value)
+                    .ok()
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(u64::MAX);
+                self.retry = Some(Duration::from_millis(millis));
+                self.event_retry = self.retry;
+            }
+            _ => (),
+        }
+        None
+    }
+
+    /// Drops the event being parsed, along with the rest of its lines up to the
+    /// blank line that would have dispatched it
+    fn discard(&mut self) {
+        self.id_buffer.clone_from(&self.last_event_id);
+        self.event.clear();
+        self.data.clear();
+        self.event_retry = None;
+        self.discarding = true;
+    }
+
+    fn dispatch(&mut self) -> Option<Event> {
+        self.last_event_id.clone_from(&self.id_buffer);
+        let retry = self.event_retry.take();
+        let event = mem::take(&mut self.event);
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut data = mem::take(&mut self.data);
+        data.pop();
+        Some(Event {
+            id: Some(self.last_event_id.clone()).filter(|id| !id.is_empty()),
+            event: if event.is_empty() {
+                "message".into()
+            } else {
+                event
+            },
+            data,
+            retry,
+        })
+    }
+}
+
+impl<S, E, L> Events<S, E, L> {
+    /// Polls for the next event, telling events that had a line too long apart from
+    /// errors of the underlying stream
+    fn poll_event<C>(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Result<Event, LineTooLong>, E>>>
+    where
+        S: Stream<Item = Result<C, E>>,
+        C: AsRef<[u8]>,
+    {
+        loop {
+            let this = self.as_mut().project();
+            match ready!(this.lines.poll_split(cx)) {
+                Some(Ok(Ok(raw))) => {
+                    if let Some(event) = this.parser.field(&raw.line) {
+                        return Poll::Ready(Some(Ok(Ok(event))));
+                    }
+                }
+                Some(Ok(Err(too_long))) => {
+                    this.parser.discard();
+                    return Poll::Ready(Some(Ok(Err(too_long))));
+                }
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                // an event that is not followed by a blank line is never dispatched
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+impl<S, C, E, L> Stream for Events<S, E, L>
+where
+    S: Stream<Item = Result<C, E>>,
+    C: AsRef<[u8]>,
+    L: Limit<E>,
+{
+    type Item = Result<Event, E>;
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.poll_event(cx)
+            .map(|event| event.map(|event| event.and_then(|event| event.map_err(L::too_long))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{stream, StreamExt};
+    use std::io;
+
+    async fn parse(chunks: Vec<&'static str>) -> Vec<Event> {
+        events(stream::iter(chunks).map(Ok::<_, io::Error>))
+            .map(Result::unwrap)
+            .collect()
+            .await
+    }
+
+    fn message(
+        id: Option<&str>,
+        data: &str,
+    ) -> Event {
+        Event {
+            id: id.map(Into::into),
+            event: "message".into(),
+            data: data.into(),
+            retry: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn it_parses_the_spec_examples() {
+        assert_eq!(
+            parse(vec!["data: YHOO\ndata: +2\ndata: 10\n\n"]).await,
+            vec![message(None, "YHOO\n+2\n10")]
+        );
+        assert_eq!(
+            parse(vec![
+                ": test stream\n\ndata: first event\nid: 1\n\n",
+                "data:second event\nid\n\ndata:  third event\n"
+            ])
+            .await,
+            vec![
+                message(Some("1"), "first event"),
+                message(None, "second event")
+            ]
+        );
+        assert_eq!(
+            parse(vec!["data\n\ndata\ndata\n\ndata:"]).await,
+            vec![message(None, ""), message(None, "\n")]
+        );
+        assert_eq!(
+            parse(vec!["data:test\n\ndata: test\n\n"]).await,
+            vec![message(None, "test"), message(None, "test")]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_parses_event_types() {
+        let events = parse(vec![
+            "event: add\ndata: 73857293\n\n",
+            "event: remove\ndata: 2153\n\n",
+            "data: 113411\n\n",
+        ])
+        .await;
+        assert_eq!(
+            events
+                .iter()
+                .map(|e| (&e.event[..], &e.data[..]))
+                .collect::<Vec<_>>(),
+            vec![
+                ("add", "73857293"),
+                ("remove", "2153"),
+                ("message", "113411")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_parses_events_split_at_every_offset() {
+        let input = "\u{feff}id: 7\r\ndata: a\rdata: b\r\n\r\n:\n\nid: 8\0\ndata: c\r\r";
+        for at in 0..=input.len() {
+            let (first, second) = input.as_bytes().split_at(at);
+            let events = events(stream::iter(vec![first, second]).map(Ok::<_, io::Error>))
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+                .await;
+            assert_eq!(
+                events,
+                vec![message(Some("7"), "a\nb"), message(Some("7"), "c")],
+                "split at {}",
+                at
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn it_tracks_ids_and_retries() {
+        let mut events = events(
+            stream::iter(vec![
+                "retry: 1500\nid: 1\n\n",
+                "retry: soon\ndata: a\n\n",
+                "retry: 3000\ndata: b\n\n",
+                "id\ndata: c\n\n",
+            ])
+            .map(Ok::<_, io::Error>),
+        );
+        let a = events.next().await.unwrap().unwrap();
+        assert_eq!((a.id.as_deref(), a.retry), (Some("1"), None));
+        assert_eq!(events.retry(), Some(Duration::from_millis(1500)));
+        let b = events.next().await.unwrap().unwrap();
+        assert_eq!(b.retry, Some(Duration::from_millis(3000)));
+        assert_eq!(events.last_event_id(), "1");
+        let c = events.next().await.unwrap().unwrap();
+        assert_eq!(c.id, None);
+        assert_eq!(events.last_event_id(), "");
+    }
+
+    #[tokio::test]
+    async fn it_only_sets_the_last_event_id_once_an_event_is_dispatched() {
+        let mut events = events(
+            stream::iter(vec!["id: 1\ndata: a\n\nid: 2\ndata: b\n"]).map(Ok::<_, io::Error>),
+        );
+        assert_eq!(
+            events.next().await.unwrap().unwrap(),
+            message(Some("1"), "a")
+        );
+        assert_eq!(events.last_event_id(), "1");
+        // the stream ended partway through the event with id 2
+        assert!(events.next().await.is_none());
+        assert_eq!(events.last_event_id(), "1");
+    }
+
+    async fn parse_limited(
+        chunks: Vec<&'static str>,
+        limit: usize,
+    ) -> Vec<Result<Event, String>> {
+        events(stream::iter(chunks).map(Ok::<_, io::Error>))
+            .max_line_length(limit)
+            .map(|event| event.map_err(|e| e.to_string()))
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn it_never_dispatches_events_with_lines_too_long() {
+        let too_long = Err("line exceeded the maximum length of 8 bytes".into());
+        assert_eq!(
+            parse_limited(vec!["data: abcdefghij\n\ndata: ok\n\n"], 8).await,
+            vec![too_long.clone(), Ok(message(None, "ok"))]
+        );
+        // with the long line in the middle of an event, and read a byte at a time
+        let input = concat!(
+            "id: 1\ndata: a\n\n",
+            "id: 2\nevent: x\nretry: 9\ndata: b\ndata: abcdefghij\ndata: c\n\n",
+            "data: d\n\n"
+        );
+        let chunks = input
+            .as_bytes()
+            .chunks(1)
+            .map(|byte| std::str::from_utf8(byte).unwrap())
+            .collect();
+        assert_eq!(
+            parse_limited(chunks, 8).await,
+            vec![
+                Ok(message(Some("1"), "a")),
+                too_long,
+                Ok(message(Some("1"), "d"))
+            ]
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_core::{ready, Stream};
+use futures_timer::Delay;
+use memchr::memchr2;
+use pin_project_lite::pin_project;
+
+use super::Event;
+use crate::{CR, LF};
+
+/// The comment sent to keep idle connections open
+const KEEP_ALIVE: &[u8] = b":\n\n";
+
+impl Event {
+    /// Creates a "message" event with `data` and no id or reconnection time
+    pub fn new(data: impl Into<String>) -> Self {
+        Event {
+            id: None,
+            event: "message".into(),
+            data: data.into(),
+            retry: None,
+        }
+    }
+
+    /// Writes this event to `buf` as it is sent in a `text/event-stream`
+    ///
+    /// Every line of `data` is written as a `data` field of its own, however it ends.
+    /// An `id` or `event` that spans more than one line is cut short at the end of its
+    /// first, so that it can not add fields to the event.
+    pub fn encode(
+        &self,
+        buf: &mut BytesMut,
+    ) {
+        if let Some(id) = &self.id {
+            field(buf, b"id", first_line(id));
+        }
+        if self.event != "message" && !self.event.is_empty() {
+            field(buf, b"event", first_line(&self.event));
+        }
+        if let Some(retry) = self.retry {
+            field(buf, b"retry", retry.as_millis().to_string().as_bytes());
+        }
+        let mut data = self.data.as_bytes();
+        while let Some(at) = memchr2(LF, CR, data) {
+            field(buf, b"data", &data[..at]);
+            let end = if data[at..].starts_with(b"\r\n") {
+                2
+            } else {
+                1
+            };
+            data = &data[at + end..];
+        }
+        field(buf, b"data", data);
+        buf.put_u8(LF);
+    }
+}
+
+fn first_line(value: &str) -> &[u8] {
+    let value = value.as_bytes();
+    &value[..memchr2(LF, CR, value).unwrap_or(value.len())]
+}
+
+fn field(
+    buf: &mut BytesMut,
+    name: &[u8],
+    value: &[u8],
+) {
+    buf.reserve(name.len() + value.len() + 3);
+    buf.put_slice(name);
+    buf.put_slice(b": ");
+    buf.put_slice(value);
+    buf.put_u8(LF);
+}
+
+/// Encodes a stream of `Event`s into chunks of a `text/event-stream`, one chunk per
+/// event, as may be sent in the body of a response. With hyper, that body is
+/// `StreamBody::new(chunks.map_ok(Frame::data))`, from `http_body_util`
+///
+/// ```
+/// use std::io;
+///
+/// use futures::{stream, StreamExt};
+/// use stream_lines::sse::{self, Event};
+///
+/// # futures::executor::block_on(async {
+/// let events = stream::iter(vec![Ok::<_, io::Error>(Event::new("hello\nworld"))]);
+/// let mut chunks = sse::encode(events);
+/// assert_eq!(
+///     chunks.next().await.unwrap().unwrap(),
+///     "data: hello\ndata: world\n\n"
+/// );
+/// # })
+/// ```
+pub fn encode<S>(s: S) -> Encode<S> {
+    Encode {
+        stream: s,
+        buf: BytesMut::new(),
+        keep_alive: None,
+        delay: None,
+    }
+}
+
+pin_project! {
+    /// Stream returned by `encode`
+    pub struct Encode<S> {
+        #[pin]
+        stream: S,
+        buf: BytesMut,
+        keep_alive: Option<Duration>,
+        delay: Option<Delay>,
+    }
+}
+
+impl<S> Encode<S> {
+    /// Sends a comment whenever `interval` passes without an event, so that proxies
+    /// do not close the connection for being idle
+    pub fn keep_alive(
+        mut self,
+        interval: Duration,
+    ) -> Self {
+        self.keep_alive = Some(interval);
+        self
+    }
+}
+
+impl<S, E> Stream for Encode<S>
+where
+    S: Stream<Item = Result<Event, E>>,
+{
+    type Item = Result<Bytes, E>;
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        if let Poll::Ready(event) = this.stream.poll_next(cx) {
+            if let (Some(interval), Some(delay)) = (this.keep_alive, this.delay.as_mut()) {
+                delay.reset(*interval);
+            }
+            return Poll::Ready(event.map(|event| {
+                event.map(|event| {
+                    event.encode(this.buf);
+                    this.buf.split().freeze()
+                })
+            }));
+        }
+        if let Some(interval) = *this.keep_alive {
+            let delay = this.delay.get_or_insert_with(|| Delay::new(interval));
+            ready!(Pin::new(&mut *delay).poll(cx));
+            delay.reset(interval);
+            return Poll::Ready(Some(Ok(Bytes::from_static(KEEP_ALIVE))));
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::events;
+    use super::*;
+    use futures::{stream, StreamExt};
+    use std::io;
+
+    fn encoded(event: &Event) -> Bytes {
+        let mut buf = BytesMut::new();
+        event.encode(&mut buf);
+        buf.freeze()
+    }
+
+    #[test]
+    fn it_encodes_fields() {
+        let event = Event {
+            id: Some("7\nevent: injected".into()),
+            event: "update".into(),
+            data: "".into(),
+            retry: Some(Duration::from_millis(2500)),
+        };
+        assert_eq!(
+            encoded(&event),
+            "id: 7\nevent: update\nretry: 2500\ndata: \n\n"
+        );
+        assert_eq!(
+            encoded(&Event::new("a\r\nb\rc\n")),
+            "data: a\ndata: b\ndata: c\ndata: \n\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn it_round_trips_events() {
+        let sent = vec![
+            Event::new("multi\nline\r\ndata"),
+            Event::new(" leading space"),
+            Event::new(""),
+            Event::new("\n"),
+            Event {
+                id: Some("1".into()),
+                event: "add".into(),
+                data: "x".into(),
+                retry: Some(Duration::from_secs(1)),
+            },
+            Event {
+                id: Some("1".into()),
+                ..Event::new(":not a comment")
+            },
+        ];
+        let chunks = encode(stream::iter(sent.clone()).map(Ok::<_, io::Error>));
+        let received = events(chunks).map(Result::unwrap).collect::<Vec<_>>().await;
+        let mut expected = sent;
+        expected[0].data = "multi\nline\ndata".into();
+        assert_eq!(received, expected);
+    }
+
+    #[tokio::test]
+    async fn it_keeps_idle_connections_alive() {
+        let chunks = encode(
+            stream::iter(vec![Ok::<_, io::Error>(Event::new("a"))]).chain(stream::pending()),
+        )
+        .keep_alive(Duration::from_millis(10))
+        .take(3)
+        .map(Result::unwrap)
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(chunks, vec!["data: a\n\n", ":\n\n", ":\n\n"]);
+    }
+}
@@ -0,0 +1,430 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::{ready, Stream};
+use futures_timer::Delay;
+use pin_project_lite::pin_project;
+
+use super::{events, Event, Events};
+use crate::{Limit, Limited, Unlimited};
+
+/// The least the wait between connections that fail in a row starts doubling from,
+/// so that a `retry` of 0 does not reconnect as fast as connections fail
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A stream of `Event`s that reconnects whenever the connection `connect` makes fails
+/// or ends, picking up where it left off
+///
+/// `connect` is handed the last event id the stream set, if any, to be sent as the
+/// `Last-Event-ID` header, and makes a new connection, resolving to its body. Between
+/// connections, the stream waits for the reconnection time a `retry` field last set,
+/// or 3 seconds, doubling it, from no less than 100 milliseconds, for every further
+/// connection in a row that fails or ends without an event, up to `max_backoff`.
+///
+/// Errors of the connection are yielded as they happen before reconnecting, while a
+/// `LineTooLong` error only skips the event it was in. Either way the stream never
+/// ends, which `try_` combinators stop short of. Filter errors out, or handle them as
+/// they arrive, to keep going.
+///
+/// ```no_run
+/// use std::io;
+///
+/// use futures::{stream, StreamExt};
+/// use stream_lines::sse;
+///
+/// # futures::executor::block_on(async {
+/// let events = sse::reconnect(|last_event_id: Option<String>| async move {
+///     // an HTTP request with a `Last-Event-ID: {last_event_id}` header
+///     Ok::<_, io::Error>(stream::iter(vec![Ok::<_, io::Error>("data: hi\n\n")]))
+/// });
+/// events
+///     .for_each(|event| async move {
+///         match event {
+///             Ok(event) => println!("{}", event.data),
+///             Err(err) => eprintln!("reconnecting after {}", err),
+///         }
+///     })
+///     .await;
+/// # })
+/// ```
+pub fn reconnect<F, Fut, S, E>(connect: F) -> Reconnect<F, Fut, S, E>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<S, E>>,
+{
+    Reconnect {
+        connect,
+        state: State::Idle,
+        last_event_id: String::new(),
+        retry: Duration::from_secs(3),
+        max_backoff: Duration::from_secs(60),
+        failures: 0,
+        limit: None,
+    }
+}
+
+pin_project! {
+    #[project = StateProj]
+    enum State<Fut, S, E, L> {
+        Idle,
+        Connecting {
+            #[pin]
+            future: Fut,
+        },
+        Streaming {
+            #[pin]
+            events: Events<S, E, L>,
+        },
+        Waiting {
+            #[pin]
+            delay: Delay,
+        },
+    }
+}
+
+pin_project! {
+    /// Stream returned by `reconnect`
+    pub struct Reconnect<F, Fut, S, E, L = Unlimited> {
+        connect: F,
+        #[pin]
+        state: State<Fut, S, E, L>,
+        last_event_id: String,
+        retry: Duration,
+        max_backoff: Duration,
+        // connections in a row that failed or ended without an event
+        failures: u32,
+        limit: Option<usize>,
+    }
+}
+
+impl<Fut, S, E, L> State<Fut, S, E, L> {
+    fn with_limit<M>(
+        self,
+        limit: Option<usize>,
+    ) -> State<Fut, S, E, M> {
+        match self {
+            State::Idle => State::Idle,
+            State::Connecting { future } => State::Connecting { future },
+            State::Streaming { events } => State::Streaming {
+                events: events.with_limit(limit),
+            },
+            State::Waiting { delay } => State::Waiting { delay },
+        }
+    }
+}
+
+impl<F, Fut, S, E, L> Reconnect<F, Fut, S, E, L> {
+    /// Sets how long to wait before reconnecting until the server sets a reconnection
+    /// time with a `retry` field. Defaults to 3 seconds
+    pub fn retry(
+        mut self,
+        retry: Duration,
+    ) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Sets the longest to wait before reconnecting after connections fail in a row,
+    /// unless the server's reconnection time is longer. Defaults to 60 seconds
+    pub fn max_backoff(
+        mut self,
+        max_backoff: Duration,
+    ) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Limits the lines events are made up of to `limit` bytes on every connection.
+    /// See [Events::max_line_length](struct.Events.html#method.max_line_length)
+    ///
+    /// # Panics
+    ///
+    /// When `limit` is 0
+    pub fn max_line_length(
+        self,
+        limit: usize,
+    ) -> Reconnect<F, Fut, S, E, Limited> {
+        assert!(limit > 0, "max_line_length must be at least 1 byte");
+        let limit = Some(limit);
+        Reconnect {
+            connect: self.connect,
+            state: self.state.with_limit(limit),
+            last_event_id: self.last_event_id,
+            retry: self.retry,
+            max_backoff: self.max_backoff,
+            failures: self.failures,
+            limit,
+        }
+    }
+
+    /// The last event id the stream set, across all connections. Empty until one is set
+    pub fn last_event_id(&self) -> &str {
+        &self.last_event_id
+    }
+}
+
+impl<F, Fut, S, C, E, L> Stream for Reconnect<F, Fut, S, E, L>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<S, E>>,
+    S: Stream<Item = Result<C, E>>,
+    C: AsRef<[u8]>,
+    L: Limit<E>,
+{
+    type Item = Result<Event, E>;
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        loop {
+            let mut this = self.as_mut().project();
+            let err = match this.state.as_mut().project() {
+                StateProj::Idle => {
+                    let last_event_id =
+                        Some(this.last_event_id.clone()).filter(|id| !id.is_empty());
+                    let future = (this.connect)(last_event_id);
+                    this.state.set(State::Connecting { future });
+                    continue;
+                }
+                StateProj::Connecting { future } => match ready!(future.poll(cx)) {
+                    Ok(stream) => {
+                        let mut events = events(stream).with_limit(*this.limit);
+                        events.resume(this.last_event_id);
+                        this.state.set(State::Streaming { events });
+                        continue;
+                    }
+                    Err(err) => Some(err),
+                },
+                StateProj::Streaming { mut events } => {
+                    let item = ready!(events.as_mut().poll_event(cx));
+                    let events = events.into_ref().get_ref();
+                    // only events that were dispatched set the id, so one the
+                    // connection ended partway through is asked for again
+                    this.last_event_id.clear();
+                    this.last_event_id.push_str(events.last_event_id());
+                    if let Some(retry) = events.retry() {
+                        *this.retry = retry;
+                    }
+                    match item {
+                        Some(Ok(Ok(event))) => {
+                            *this.failures = 0;
+                            return Poll::Ready(Some(Ok(event)));
+                        }
+                        // the rest of the event is skipped, so the connection can
+                        // carry on, where reconnecting would have it sent again
+                        Some(Ok(Err(too_long))) => {
+                            return Poll::Ready(Some(Err(L::too_long(too_long))));
+                        }
+                        Some(Err(err)) => Some(err),
+                        None => None,
+                    }
+                }
+                StateProj::Waiting { delay } => {
+                    ready!(delay.poll(cx));
+                    this.state.set(State::Idle);
+                    continue;
+                }
+            };
+            // the connection failed or ended
+            let backoff = if *this.failures == 0 {
+                *this.retry
+            } else {
+                (*this.retry)
+                    .max(MIN_BACKOFF)
+                    .saturating_mul(1 << (*this.failures).min(16))
+                    .min((*this.max_backoff).max(*this.retry))
+            };
+            *this.failures = this.failures.saturating_add(1);
+            this.state.set(State::Waiting {
+                delay: Delay::new(backoff),
+            });
+            if let Some(err) = err {
+                return Poll::Ready(Some(Err(err)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, stream, StreamExt, TryStreamExt};
+    use std::collections::VecDeque;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    #[tokio::test]
+    async fn it_reconnects_with_the_last_event_id() {
+        let mut connections = VecDeque::from(vec![
+            Ok(vec!["id: 1\ndata: a\n\nretry: 5\n\n"]),
+            Err(io::Error::other("refused")),
+            Ok(vec![]),
+            Ok(vec!["data: b\n\n"]),
+            // ends partway through the event with id 2, which is never received
+            Ok(vec!["id: 2\ndata: c\n"]),
+            Ok(vec!["data: d\n\n"]),
+        ]);
+        let ids = Arc::new(Mutex::new(vec![]));
+        let connected = ids.clone();
+        let events = reconnect(move |id| {
+            connected.lock().unwrap().push(id);
+            future::ready(
+                connections
+                    .pop_front()
+                    .unwrap_or_else(|| Ok(vec![]))
+                    .map(|chunks| stream::iter(chunks).map(Ok::<_, io::Error>)),
+            )
+        })
+        .retry(Duration::from_secs(60))
+        .take(4)
+        .map(|event| {
+            event
+                .map(|event| (event.id, event.data))
+                .map_err(|e| e.to_string())
+        })
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(
+            events,
+            vec![
+                Ok((Some("1".into()), "a".into())),
+                Err("refused".into()),
+                Ok((Some("1".into()), "b".into())),
+                Ok((Some("1".into()), "d".into())),
+            ]
+        );
+        assert_eq!(
+            *ids.lock().unwrap(),
+            vec![
+                None,
+                Some("1".into()),
+                Some("1".into()),
+                Some("1".into()),
+                Some("1".into()),
+                Some("1".into())
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_keeps_the_connection_after_an_event_too_long() {
+        let connections = Arc::new(Mutex::new(0));
+        let connected = connections.clone();
+        let events = reconnect(move |_| {
+            *connected.lock().unwrap() += 1;
+            future::ok::<_, io::Error>(
+                stream::iter(vec![
+                    "id: 1\ndata: a\n\n",
+                    "id: 2\ndata: abcdefghijkl\n\n",
+                    "id: 3\ndata: ok\n\n",
+                ])
+                .map(Ok::<_, io::Error>),
+            )
+        })
+        .max_line_length(8)
+        .take(3)
+        .map(|event| {
+            event
+                .map(|event| (event.id, event.data))
+                .map_err(|e| e.to_string())
+        })
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(
+            events,
+            vec![
+                Ok((Some("1".into()), "a".into())),
+                Err("line exceeded the maximum length of 8 bytes".into()),
+                Ok((Some("3".into()), "ok".into())),
+            ]
+        );
+        assert_eq!(*connections.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn it_backs_off_when_told_to_retry_at_once() {
+        let connections = Arc::new(Mutex::new(vec![]));
+        let connected = connections.clone();
+        let started = std::time::Instant::now();
+        let events = reconnect(move |_| {
+            connected.lock().unwrap().push(started.elapsed());
+            let chunks = match connected.lock().unwrap().len() {
+                1 => vec!["retry: 0\ndata: a\n\n"],
+                4 => vec!["data: b\n\n"],
+                _ => vec![],
+            };
+            future::ok::<_, io::Error>(stream::iter(chunks).map(Ok::<_, io::Error>))
+        })
+        .take(2)
+        .map(|event| event.unwrap().data)
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(events, vec!["a", "b"]);
+        // reconnects at once, then after 200 and 400 milliseconds
+        let connections = connections.lock().unwrap();
+        assert!(connections[3] - connections[1] >= Duration::from_millis(600));
+    }
+
+    #[tokio::test]
+    async fn it_reconnects_to_a_server() {
+        use http_body_util::{BodyExt, Empty, Full};
+        use hyper::body::Bytes;
+        use hyper::{Request, Response};
+        use hyper_util::client::legacy::Client;
+        use hyper_util::rt::{TokioExecutor, TokioIo};
+        use std::convert::Infallible;
+        use std::error::Error;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::net::TcpListener;
+
+        type BoxErr = Box<dyn Error + Send + Sync>;
+
+        // a stand-in server that ends every response after a single event, echoing the
+        // `Last-Event-ID` header it was sent
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let uri = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let requests = requests.clone();
+                let service = hyper::service::service_fn(move |req: Request<_>| {
+                    let last_event_id = req
+                        .headers()
+                        .get("Last-Event-ID")
+                        .map(|id| id.to_str().unwrap().to_owned());
+                    let body = format!(
+                        "retry: 10\nid: {}\ndata: {:?}\n\n",
+                        requests.fetch_add(1, Ordering::SeqCst) + 1,
+                        last_event_id
+                    );
+                    future::ok::<_, Infallible>(Response::new(Full::new(Bytes::from(body))))
+                });
+                tokio::spawn(
+                    hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service),
+                );
+            }
+        });
+
+        let client = Client::builder(TokioExecutor::new()).build_http::<Empty<Bytes>>();
+        let events = reconnect(move |id| {
+            let client = client.clone();
+            let mut req = Request::get(&uri);
+            if let Some(id) = id {
+                req = req.header("Last-Event-ID", id);
+            }
+            async move {
+                let resp = client.request(req.body(Empty::new())?).await?;
+                Ok::<_, BoxErr>(resp.into_body().into_data_stream().map_err(BoxErr::from))
+            }
+        })
+        .take(3)
+        .map(|event| event.unwrap().data)
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(events, vec!["None", "Some(\"1\")", "Some(\"2\")"]);
+    }
+}